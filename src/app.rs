@@ -1,110 +1,297 @@
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use async_std::sync::Mutex;
 use mpris_server::{Metadata, PlaybackStatus, Property, Signal, Time, TrackId};
 
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::error::{Error, Severity};
 use crate::player::Player;
-use crate::queue::Queue;
+use crate::playlist::{self, Entry, Format};
+use crate::queue::{Key, Queue};
+use crate::scanner::Scanner;
 use crate::song::Song;
-use crate::ui::{Event, Ui};
-use crate::Message;
+use crate::ui::{Event, Export, Ui};
+use crate::watcher::Watcher;
+use crate::{Message, Source};
+
+/// How many songs in a row can fail to play before giving up.
+const MAX_FAILURES: usize = 8;
+
+/// Which way to move past songs that can't be played.
+#[derive(Clone, Copy)]
+enum Direction {
+    Forward,
+    Back,
+}
 
 pub enum Effect {
     Signal(Signal),
     Changed(Vec<Property>),
+
+    /// A song started playing.
+    Notify(Box<Song>),
 }
 
 pub struct App {
     player: Mutex<Player>,
     queue: Mutex<Queue>,
     ui: Mutex<Ui>,
+    watcher: Mutex<Option<Watcher>>,
+    scanner: Mutex<Scanner>,
 
+    /// The song last announced with [`Effect::Notify`].
+    announced: Mutex<Option<Key>>,
+
+    pub config: Arc<Config>,
     pub quit: AtomicBool,
     pub effects: Mutex<Vec<Effect>>,
 }
 
 impl App {
     pub async fn new(path: Option<impl AsRef<Path>>) -> Self {
-        let mut player = Player::new();
-        let mut queue = Queue::new();
-        let mut ui = Ui::new().await;
+        let (config, messages) = Config::load();
+        let config = Arc::new(config);
+        let player = Player::new();
+        let mut queue = Queue::new(config.clone());
+        let mut ui = Ui::new(&config).await;
 
-        if let Some(path) = path {
-            for message in queue.load(path) {
-                ui.add_message(message);
-            }
+        for message in messages {
+            ui.add_message(message);
         }
 
-        queue.queue_all();
+        // songs are added as they're found, so keep them sorted from the start
         queue.sort_songs();
-        queue.advance();
-
-        if let Some(song) = queue.current() {
-            if let Err(e) = player.play(song) {
-                ui.add_message(Message::new(format!(
-                    "failed to play {}: {e}",
-                    song.path.display()
-                )));
-            }
+
+        let cache = config.cache.then(|| {
+            Cache::load()
+                .map_err(|e| {
+                    let path = Cache::path().unwrap_or_default();
+                    let error = Error::io("load library cache", path, e);
+                    ui.add_message(Message::from(error).source(Source::Library))
+                })
+                .unwrap_or_default()
+        });
+        let mut scanner = Scanner::new(config.clone(), cache);
+        if let Some(path) = path {
+            scanner.scan(path.as_ref());
         }
 
+        let watcher = Watcher::new()
+            .map_err(|source| ui.add_message(Error::Watch { dirs: 0, source }.into()))
+            .ok();
+
         Self {
             player: Mutex::new(player),
             queue: Mutex::new(queue),
             ui: Mutex::new(ui),
+            watcher: Mutex::new(watcher),
+            scanner: Mutex::new(scanner),
+
+            announced: Mutex::new(None),
 
+            config,
             quit: AtomicBool::new(false),
             effects: Mutex::new(Vec::with_capacity(4)),
         }
     }
 
-    fn play(&self, song: &Song, player: &mut Player, ui: &mut Ui) {
-        if let Err(e) = player.play(song) {
-            ui.add_message(Message::new(format!(
-                "failed to play song {}: {e}",
-                song.path.display()
-            )));
+    /// Plays the current song, moving on to the next (or previous) playable
+    /// one if it can't be played, and loads the song after it.
+    fn play(&self, player: &mut Player, queue: &mut Queue, ui: &mut Ui, direction: Direction) {
+        for failures in 0..MAX_FAILURES {
+            let Some(id) = queue.current_id() else {
+                if failures > 0 {
+                    // ran out of songs to try
+                    player.end();
+                }
+                return;
+            };
+
+            let Some(song) = queue.get(id) else {
+                return;
+            };
+            match player.play(song) {
+                Ok(()) => {
+                    if song.broken.is_some() {
+                        queue.set_broken(id, None);
+                    }
+                    self.next(player, queue, ui);
+                    return;
+                }
+                Err(source) => {
+                    let reason = source.to_string();
+                    let error = Error::Decode {
+                        action: "play",
+                        path: song.path.clone(),
+                        source,
+                    };
+                    ui.add_message(Message::from(error).song(song));
+                    queue.set_broken(id, Some(reason));
+                    match direction {
+                        Direction::Forward => {
+                            if let Some(message) = queue.advance() {
+                                ui.add_message(message);
+                            }
+                        }
+                        Direction::Back => queue.previous(),
+                    }
+                }
+            }
         }
+
+        // don't leave a song that was never played as the current one
+        queue.stop();
+        player.end();
+        ui.add_message(
+            Message::new(format!(
+                "stopped after {MAX_FAILURES} unplayable songs in a row"
+            ))
+            .level(Severity::Warning)
+            .source(Source::Player),
+        );
     }
 
-    fn next(&self, song: &Song, player: &mut Player, ui: &mut Ui) {
-        if let Err(e) = player.load_next(song) {
-            ui.add_message(Message::new(format!(
-                "failed to load song {}: {e}",
-                song.path.display()
-            )));
+    /// Loads the next song ahead of time.
+    fn next(&self, player: &mut Player, queue: &mut Queue, ui: &mut Ui) {
+        let Some(id) = queue.next_id() else {
+            return;
+        };
+
+        let Some(song) = queue.get(id) else {
+            return;
+        };
+        if let Err(source) = player.load_next(song) {
+            let reason = source.to_string();
+            let error = Error::Decode {
+                action: "load",
+                path: song.path.clone(),
+                source,
+            };
+            ui.add_message(Message::from(error).song(song));
+            queue.set_broken(id, Some(reason));
         }
     }
 
     fn advance(&self, player: &mut Player, queue: &mut Queue, ui: &mut Ui) {
-        queue.advance();
-        if let Some(song) = queue.current() {
-            self.play(song, player, ui);
-        }
-        if let Some(song) = queue.next() {
-            self.next(song, player, ui);
+        if let Some(message) = queue.advance() {
+            ui.add_message(message);
         }
+        self.play(player, queue, ui, Direction::Forward);
     }
 
     fn previous(&self, player: &mut Player, queue: &mut Queue, ui: &mut Ui) {
         queue.previous();
-        if let Some(song) = queue.current() {
-            if let Err(e) = player.play(song) {
-                ui.add_message(Message::new(format!(
-                    "failed to play song {}: {e}",
-                    song.path.display()
-                )));
+        self.play(player, queue, ui, Direction::Back);
+    }
+
+    /// Plays the song `id` right away, saying so if it couldn't be played and
+    /// another one was played instead.
+    fn play_now(&self, id: Key, player: &mut Player, queue: &mut Queue, ui: &mut Ui) {
+        queue.play(id);
+        self.play(player, queue, ui, Direction::Forward);
+
+        if queue.current_id() != Some(id) {
+            if let Some(song) = queue.get(id) {
+                let message = format!("skipped {}, as it can't be played", song.name);
+                ui.add_message(
+                    Message::new(message)
+                        .level(Severity::Warning)
+                        .source(Source::Player)
+                        .song(song),
+                );
+            }
+        }
+    }
+
+    /// Removes songs from the library, moving on if the current song was one
+    /// of them. Returns how many songs were removed.
+    fn remove(
+        &self,
+        remove: impl FnOnce(&mut Queue) -> Vec<Key>,
+        player: &mut Player,
+        queue: &mut Queue,
+        ui: &mut Ui,
+    ) -> usize {
+        let playing = queue.current_id().is_some();
+        let removed = remove(queue).len();
+        ui.add_message(Message::new(format!("removed {removed} songs")).source(Source::Library));
+
+        if playing && queue.current_id().is_none() {
+            player.end();
+            self.advance(player, queue, ui);
+        }
+
+        removed
+    }
+
+    /// Applies changes on disk to the library: new files are loaded, deleted
+    /// ones removed, and edited playlists reloaded. Returns whether any songs
+    /// were removed.
+    fn rescan(
+        &self,
+        paths: HashSet<PathBuf>,
+        player: &mut Player,
+        queue: &mut Queue,
+        ui: &mut Ui,
+    ) -> bool {
+        let mut removed = false;
+        for path in paths {
+            let known = queue.has_playlist(&path) || queue.has_dir(&path);
+            let watched = known || path.parent().is_some_and(|dir| queue.has_dir(dir));
+            if !watched {
+                continue;
             }
-            if let Some(song) = queue.next() {
-                if let Err(e) = player.load_next(song) {
-                    ui.add_message(Message::new(format!(
-                        "failed to load song {}: {e}",
-                        song.path.display()
-                    )));
+
+            if !path.exists() {
+                if known || queue.find(&path).is_some() {
+                    self.remove(
+                        |queue| {
+                            let mut removed = queue.remove_source(&path);
+                            removed.extend(queue.remove_path(&path));
+                            removed
+                        },
+                        player,
+                        queue,
+                        ui,
+                    );
+                    removed = true;
+                }
+            } else if queue.has_playlist(&path) {
+                for message in queue.reload_playlist(&path) {
+                    ui.add_message(message);
+                }
+            } else if !known
+                && queue.find(&path).is_none()
+                && queue.loadable(&path)
+                // playlists saved into the library would duplicate its songs
+                && (path.is_dir() || Format::from_path(&path).is_none())
+            {
+                for message in queue.load(&path) {
+                    ui.add_message(message);
                 }
             }
         }
+
+        removed
+    }
+
+    /// Like [`App::remove`], for outside the main loop.
+    pub async fn remove_songs(&self, remove: impl FnOnce(&mut Queue) -> Vec<Key>) -> usize {
+        let mut player = self.player.lock().await;
+        let mut queue = self.queue.lock().await;
+        let mut ui = self.ui.lock().await;
+
+        let removed = self.remove(remove, &mut player, &mut queue, &mut ui);
+        self.effects.lock().await.push(Effect::Changed(vec![
+            Property::Metadata(self.meta(&player, &queue)),
+            Property::PlaybackStatus(self.status(&player)),
+        ]));
+
+        removed
     }
 
     pub async fn add_message(&self, message: Message) {
@@ -153,6 +340,57 @@ impl App {
             self.advance(&mut player, &mut queue, &mut ui);
         }
 
+        let mut scanner = self.scanner.lock().await;
+        let (results, messages) = scanner.poll();
+        let found = !results.is_empty();
+        for scanned in results {
+            for message in queue.add_scanned(scanned) {
+                ui.add_message(message);
+            }
+        }
+        ui.set_progress(scanner.progress());
+        for message in messages {
+            ui.add_message(message);
+        }
+        drop(scanner);
+
+        // start playing as soon as there's something to play
+        if found && queue.current_id().is_none() && queue.songs().next().is_some() {
+            self.advance(&mut player, &mut queue, &mut ui);
+            self.effects.lock().await.push(Effect::Changed(vec![
+                Property::Metadata(self.meta(&player, &queue)),
+                Property::PlaybackStatus(self.status(&player)),
+            ]));
+        }
+
+        if let Some(watcher) = &mut *self.watcher.lock().await {
+            let changes = watcher.changes();
+            if self.rescan(changes, &mut player, &mut queue, &mut ui) {
+                self.effects.lock().await.push(Effect::Changed(vec![
+                    Property::Metadata(self.meta(&player, &queue)),
+                    Property::PlaybackStatus(self.status(&player)),
+                ]));
+            }
+
+            if let Some(message) = watcher.sync(&queue) {
+                ui.add_message(message);
+            }
+        }
+
+        // however the song changed, as long as it's playing
+        let current = queue.current_id();
+        let mut announced = self.announced.lock().await;
+        if current != *announced {
+            *announced = current;
+            if let Some(song) = queue.current().filter(|song| song.broken.is_none()) {
+                self.effects
+                    .lock()
+                    .await
+                    .push(Effect::Notify(Box::new(song.clone())));
+            }
+        }
+        drop(announced);
+
         ui.draw(&queue, &player);
         ui.flush();
 
@@ -196,12 +434,16 @@ impl App {
                 ]))
             }
             Some(Event::PlayNow(id)) => {
-                let song = queue.play(id);
-                if let Err(e) = player.play(song) {
-                    ui.add_message(Message::new(format!(
-                        "failed to play song {}: {e}",
-                        song.path.display()
-                    )));
+                self.play_now(id, &mut player, &mut queue, &mut ui);
+
+                Some(Effect::Changed(vec![
+                    Property::Metadata(self.meta(&player, &queue)),
+                    Property::PlaybackStatus(self.status(&player)),
+                ]))
+            }
+            Some(Event::PlayAll(ids)) => {
+                if queue.play_all(&ids).is_some() {
+                    self.play(&mut player, &mut queue, &mut ui, Direction::Forward);
                 }
 
                 Some(Effect::Changed(vec![
@@ -210,13 +452,8 @@ impl App {
                 ]))
             }
             Some(Event::PlayNext(id)) => {
-                let song = queue.set_next(id);
-                if let Err(e) = player.load_next(song) {
-                    ui.add_message(Message::new(format!(
-                        "failed to load song {}: {e}",
-                        song.path.display()
-                    )));
-                }
+                queue.set_next(id);
+                self.next(&mut player, &mut queue, &mut ui);
 
                 None
             }
@@ -224,6 +461,17 @@ impl App {
                 queue.queue(id, true);
                 None
             }
+            Some(Event::PlayNextAll(ids)) => {
+                if queue.set_next_all(&ids).is_some() {
+                    self.next(&mut player, &mut queue, &mut ui);
+                }
+
+                None
+            }
+            Some(Event::QueueAll(ids)) => {
+                queue.queue_many(&ids, true);
+                None
+            }
             Some(Event::SeekRight) => {
                 player.seek_by(5.0);
                 player.time_info().map(|(secs, _)| {
@@ -238,6 +486,76 @@ impl App {
                     Effect::Signal(Signal::Seeked { position: time })
                 })
             }
+            Some(Event::Save(export, path)) => {
+                let home = dirs::home_dir();
+                let songs: Vec<_> = match export {
+                    Export::Queue => {
+                        let mut songs: Vec<Entry> = Vec::new();
+                        for song in queue.current().into_iter().chain(queue.upcoming()) {
+                            // already saved as the `#EXTNEXT` of the one before
+                            let last = songs.last().and_then(|entry| entry.next.as_ref());
+                            if last != Some(&song.path) {
+                                songs.push(Entry::from(song));
+                            }
+                        }
+                        songs
+                    }
+                    Export::History => queue.history().map(Entry::from).collect(),
+                    Export::Songs(ids) => ids
+                        .into_iter()
+                        .filter_map(|id| queue.get(id))
+                        .map(Entry::from)
+                        .collect(),
+                };
+
+                let result = playlist::write_m3u(&path, None, songs, home.as_deref());
+                ui.add_message(
+                    match result {
+                        Ok(n) => Message::new(format!("saved {n} songs to {}", path.display())),
+                        Err(e) => Message::from(Error::io("save playlist to", path, e)),
+                    }
+                    .source(Source::Playlist),
+                );
+
+                None
+            }
+            Some(Event::Open(path)) => {
+                self.scanner.lock().await.scan(path);
+                None
+            }
+            Some(Event::Remove(ids)) => {
+                self.remove(
+                    |queue| queue.remove_many(&ids),
+                    &mut player,
+                    &mut queue,
+                    &mut ui,
+                );
+
+                Some(Effect::Changed(vec![
+                    Property::Metadata(self.meta(&player, &queue)),
+                    Property::PlaybackStatus(self.status(&player)),
+                ]))
+            }
+            Some(Event::Unload(path)) => {
+                self.remove(
+                    |queue| {
+                        let removed = queue.remove_source(&path);
+                        if removed.is_empty() {
+                            queue.remove_path(&path)
+                        } else {
+                            removed
+                        }
+                    },
+                    &mut player,
+                    &mut queue,
+                    &mut ui,
+                );
+
+                Some(Effect::Changed(vec![
+                    Property::Metadata(self.meta(&player, &queue)),
+                    Property::PlaybackStatus(self.status(&player)),
+                ]))
+            }
             None => None,
         } {
             self.effects.lock().await.push(effect);
@@ -259,7 +577,7 @@ mod mpris {
 
     use crate::song::Song;
 
-    use super::{App, Effect};
+    use super::{App, Direction, Effect};
 
     impl RootInterface for App {
         async fn identity(&self) -> FResult<String> {
@@ -485,11 +803,9 @@ mod mpris {
             let mut queue = self.queue.lock().await;
             let mut ui = self.ui.lock().await;
 
-            let id = queue.add_song(Song::new(path.as_ref()));
-            queue.play(id);
-            if let Some(song) = queue.current() {
-                self.play(song, &mut player, &mut ui);
-            }
+            let song = Song::new(path.as_ref()).map_err(|e| FError::InvalidArgs(e.to_string()))?;
+            let id = queue.add_song(song);
+            self.play_now(id, &mut player, &mut queue, &mut ui);
 
             let status = match (player.playing(), player.finished()) {
                 (false, false) => PlaybackStatus::Paused,
@@ -552,9 +868,7 @@ mod mpris {
             let mut ui = self.ui.lock().await;
 
             queue.shuffle();
-            if let Some(song) = queue.current() {
-                self.play(song, &mut player, &mut ui);
-            }
+            self.play(&mut player, &mut queue, &mut ui, Direction::Forward);
 
             self.effects
                 .lock()
@@ -603,3 +917,79 @@ mod mpris {
         }
     }
 }
+
+pub mod control {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use mpris_server::zbus::fdo::{self, Error as FError};
+    use mpris_server::zbus::zvariant::ObjectPath;
+    use mpris_server::Server;
+
+    use super::App;
+
+    /// cramp's own D-Bus interface, for what MPRIS doesn't cover.
+    pub struct Control(pub Arc<Server<App>>);
+
+    #[zbus::interface(name = "com.cramp.Library1")]
+    impl Control {
+        /// Removes a song from the library by its MPRIS track ID.
+        async fn remove_track(&self, track_id: ObjectPath<'_>) -> fdo::Result<()> {
+            let Some(id) = track_id
+                .as_str()
+                .strip_prefix("/com/cramp/tracks/trackid")
+                .and_then(|id| id.parse().ok())
+            else {
+                return Err(FError::InvalidArgs(track_id.to_string()));
+            };
+
+            let removed = self
+                .0
+                .imp()
+                .remove_songs(|queue| queue.remove(id).map(|_| vec![id]).unwrap_or_default())
+                .await;
+
+            if removed == 0 {
+                Err(FError::InvalidArgs(track_id.to_string()))
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Removes every song inside a directory, returning how many there were.
+        async fn remove_path(&self, path: String) -> fdo::Result<u32> {
+            let path = absolute_path(&path)?;
+            Ok(self
+                .0
+                .imp()
+                .remove_songs(|queue| queue.remove_path(path))
+                .await as u32)
+        }
+
+        /// Removes every song loaded from a playlist, returning how many there
+        /// were.
+        async fn unload_playlist(&self, path: String) -> fdo::Result<u32> {
+            let path = absolute_path(&path)?;
+            Ok(self
+                .0
+                .imp()
+                .remove_songs(|queue| queue.remove_source(path))
+                .await as u32)
+        }
+    }
+
+    /// Checks that a path given over D-Bus is absolute, as there's no telling
+    /// what a relative one (or an empty one, which is every path's prefix)
+    /// would be relative to.
+    fn absolute_path(path: &str) -> fdo::Result<&Path> {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            Ok(path)
+        } else {
+            Err(FError::InvalidArgs(format!(
+                "not an absolute path: {}",
+                path.display()
+            )))
+        }
+    }
+}
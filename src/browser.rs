@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+
+use crate::queue::{Key, Queue};
+use crate::song::Song;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+    /// Mirrors the directory tree the songs were loaded from.
+    Folder,
+
+    /// Groups by artist, then album.
+    Artist,
+}
+
+#[derive(Default)]
+pub struct Node {
+    pub name: String,
+    pub children: Vec<Node>,
+    pub songs: Vec<Key>,
+}
+
+pub enum Entry<'a> {
+    Node(&'a Node),
+    Song(Key),
+}
+
+impl Node {
+    /// The number of entries (child nodes, then songs) in this node.
+    pub fn len(&self) -> usize {
+        self.children.len() + self.songs.len()
+    }
+
+    pub fn entry(&self, idx: usize) -> Option<Entry<'_>> {
+        if let Some(node) = self.children.get(idx) {
+            Some(Entry::Node(node))
+        } else {
+            self.songs
+                .get(idx - self.children.len())
+                .map(|id| Entry::Song(*id))
+        }
+    }
+
+    /// Returns every song under this node, in display order.
+    pub fn all_songs(&self) -> Vec<Key> {
+        let mut songs = Vec::new();
+        self.collect(&mut songs);
+        songs
+    }
+
+    fn collect(&self, songs: &mut Vec<Key>) {
+        for child in &self.children {
+            child.collect(songs);
+        }
+        songs.extend_from_slice(&self.songs);
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    children: BTreeMap<String, Builder>,
+    songs: Vec<Key>,
+}
+
+impl Builder {
+    fn insert(&mut self, path: impl IntoIterator<Item = String>, id: Key) {
+        let mut node = self;
+        for name in path {
+            node = node.children.entry(name).or_default();
+        }
+        node.songs.push(id);
+    }
+
+    fn build(self, name: String) -> Node {
+        Node {
+            name,
+            children: self
+                .children
+                .into_iter()
+                .map(|(name, child)| child.build(name))
+                .collect(),
+            songs: self.songs,
+        }
+    }
+
+    /// Skips over the directories every song has in common.
+    fn collapse(mut self, mut name: String) -> Node {
+        while self.songs.is_empty() && self.children.len() == 1 {
+            let (child_name, child) = self.children.pop_first().unwrap();
+            if !name.is_empty() && !name.ends_with('/') {
+                name.push('/');
+            }
+            name.push_str(&child_name);
+            self = child;
+        }
+
+        self.build(name)
+    }
+}
+
+fn folder_path(song: &Song) -> Vec<String> {
+    song.path
+        .parent()
+        .into_iter()
+        .flat_map(|p| p.iter())
+        .map(|c| c.to_string_lossy().to_string())
+        .collect()
+}
+
+fn artist_path(song: &Song) -> Vec<String> {
+    vec![
+        song.artist
+            .clone()
+            .unwrap_or_else(|| "<unknown artist>".into()),
+        song.album
+            .clone()
+            .unwrap_or_else(|| "<unknown album>".into()),
+    ]
+}
+
+pub struct Browser {
+    grouping: Grouping,
+    root: Node,
+    generation: Option<usize>,
+
+    /// The child indices leading from `root` to the current node.
+    path: Vec<usize>,
+
+    pub nodes: usize,
+    pub node_idx: usize,
+}
+
+impl Browser {
+    pub fn new() -> Self {
+        Self {
+            grouping: Grouping::Folder,
+            root: Node::default(),
+            generation: None,
+
+            path: Vec::new(),
+
+            nodes: 0,
+            node_idx: 0,
+        }
+    }
+
+    pub fn grouping(&self) -> Grouping {
+        self.grouping
+    }
+
+    pub fn toggle_grouping(&mut self) {
+        self.grouping = match self.grouping {
+            Grouping::Folder => Grouping::Artist,
+            Grouping::Artist => Grouping::Folder,
+        };
+        self.generation = None;
+        self.path.clear();
+        self.nodes = 0;
+        self.node_idx = 0;
+    }
+
+    /// Rebuilds the tree if the library changed since it was last built.
+    pub fn refresh(&mut self, queue: &Queue) {
+        if self.generation == Some(queue.generation()) {
+            return;
+        }
+
+        let mut builder = Builder::default();
+        for (id, song) in queue.songs() {
+            let path = match self.grouping {
+                Grouping::Folder => folder_path(song),
+                Grouping::Artist => artist_path(song),
+            };
+            builder.insert(path, id);
+        }
+
+        self.root = match self.grouping {
+            Grouping::Folder => builder.collapse(String::new()),
+            Grouping::Artist => builder.build("artists".into()),
+        };
+        self.generation = Some(queue.generation());
+
+        // the tree may have changed shape underneath us
+        let mut node = &self.root;
+        for (depth, idx) in self.path.iter().enumerate() {
+            let Some(child) = node.children.get(*idx) else {
+                self.path.truncate(depth);
+                self.nodes = 0;
+                self.node_idx = 0;
+                break;
+            };
+            node = child;
+        }
+
+        if self.node_idx >= node.len() {
+            self.node_idx = node.len().saturating_sub(1);
+            self.nodes = self.nodes.min(self.node_idx);
+        }
+    }
+
+    pub fn current(&self) -> &Node {
+        self.path
+            .iter()
+            .fold(&self.root, |node, idx| &node.children[*idx])
+    }
+
+    /// The names of the nodes leading to the current one.
+    pub fn title(&self) -> String {
+        let mut node = &self.root;
+        let mut title = node.name.clone();
+        for idx in &self.path {
+            node = &node.children[*idx];
+            title.push_str(" / ");
+            title.push_str(&node.name);
+        }
+        title
+    }
+
+    pub fn selected(&self) -> Option<Entry<'_>> {
+        self.current().entry(self.node_idx)
+    }
+
+    /// Returns the songs under the selected entry.
+    pub fn selected_songs(&self) -> Vec<Key> {
+        match self.selected() {
+            Some(Entry::Node(node)) => node.all_songs(),
+            Some(Entry::Song(id)) => vec![id],
+            None => Vec::new(),
+        }
+    }
+
+    /// Descends into the selected node, or returns the selected song.
+    pub fn enter(&mut self) -> Option<Key> {
+        match self.selected()? {
+            Entry::Node(_) => {
+                self.path.push(self.node_idx);
+                self.nodes = 0;
+                self.node_idx = 0;
+                None
+            }
+            Entry::Song(id) => Some(id),
+        }
+    }
+
+    /// Returns to the parent node, selecting the one we came from.
+    pub fn leave(&mut self) {
+        if let Some(idx) = self.path.pop() {
+            self.node_idx = idx;
+            self.nodes = idx.saturating_sub(5);
+        }
+    }
+
+    pub fn down(&mut self) {
+        if self.node_idx + 1 < self.current().len() {
+            self.node_idx += 1;
+            if self.node_idx > 5 {
+                self.nodes += 1;
+            }
+        }
+    }
+
+    pub fn up(&mut self) {
+        self.node_idx = self.node_idx.saturating_sub(1);
+        if self.node_idx < self.nodes {
+            self.nodes = self.node_idx;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::Config;
+
+    fn names(node: &Node) -> Vec<&str> {
+        node.children
+            .iter()
+            .map(|child| child.name.as_str())
+            .collect()
+    }
+
+    fn queue() -> Queue {
+        let mut queue = Queue::new(Arc::new(Config::default()));
+        for (path, artist, album) in [
+            ("/music/a/1.mp3", Some("B"), Some("Y")),
+            ("/music/a/2.mp3", Some("A"), Some("X")),
+            ("/music/b/3.mp3", Some("A"), Some("Z")),
+            ("/music/b/4.mp3", None, None),
+        ] {
+            let song = Song::new(path)
+                .unwrap()
+                .artist(artist.map(Into::into))
+                .album(album.map(Into::into));
+            queue.add_song(song);
+        }
+        queue
+    }
+
+    #[test]
+    fn groups_by_folder() {
+        let queue = queue();
+        let mut browser = Browser::new();
+        browser.refresh(&queue);
+
+        assert_eq!(browser.current().name, "/music");
+        assert_eq!(names(browser.current()), ["a", "b"]);
+        assert_eq!(browser.current().all_songs().len(), 4);
+    }
+
+    #[test]
+    fn groups_by_artist_and_album() {
+        let queue = queue();
+        let mut browser = Browser::new();
+        browser.toggle_grouping();
+        browser.refresh(&queue);
+
+        assert_eq!(names(browser.current()), ["<unknown artist>", "A", "B"]);
+        assert_eq!(names(&browser.current().children[1]), ["X", "Z"]);
+        assert_eq!(names(&browser.current().children[0]), ["<unknown album>"]);
+    }
+}
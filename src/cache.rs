@@ -0,0 +1,482 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, Metadata};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::tags::Tags;
+
+/// The first line of the cache file, bumped whenever the format changes.
+const HEADER: &str = "cramp library cache 2";
+
+/// When a file or folder was last modified, and its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    mtime: Duration,
+    size: u64,
+}
+
+impl Stamp {
+    pub fn new(meta: &Metadata) -> Option<Self> {
+        Some(Self {
+            mtime: meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?,
+            size: meta.len(),
+        })
+    }
+
+    pub fn of(path: &Path) -> Option<Self> {
+        Self::new(&fs::metadata(path).ok()?)
+    }
+}
+
+/// A file or folder in a cached folder.
+#[derive(Clone)]
+pub struct Entry {
+    pub name: OsString,
+    pub dir: bool,
+    pub symlink: bool,
+    pub stamp: Option<Stamp>,
+
+    /// Whether the file starts like audio, if it was checked at `stamp`.
+    pub audio: Option<bool>,
+
+    /// The file's tags, if they were read at `stamp`.
+    pub tags: Option<Tags>,
+}
+
+impl Entry {
+    /// Forgets what was found out about the file at `path` if it changed
+    /// since.
+    pub fn refresh(&mut self, path: &Path) {
+        let stamp = Stamp::of(path);
+        if stamp.is_none() || stamp != self.stamp {
+            self.stamp = stamp;
+            self.audio = None;
+            self.tags = None;
+        }
+    }
+
+    /// Whether the file starts like audio, checking with `sniff` only if it
+    /// wasn't checked since it last changed.
+    pub fn audio(&mut self, path: &Path, sniff: impl FnOnce(&Path) -> bool) -> bool {
+        *self.audio.get_or_insert_with(|| sniff(path))
+    }
+
+    /// The file's tags, reading them only if they weren't read since it last
+    /// changed.
+    pub fn tags(&mut self, path: &Path) -> Tags {
+        self.tags.get_or_insert_with(|| Tags::read(path)).clone()
+    }
+}
+
+/// The contents of a folder, as of when it was last modified.
+#[derive(Clone)]
+pub struct Dir {
+    pub stamp: Stamp,
+    pub entries: Vec<Entry>,
+}
+
+/// The folders scanned before, so unchanged ones don't have to be read again.
+///
+/// Kept in `~/.cache/cramp/library`.
+#[derive(Default)]
+pub struct Cache {
+    dirs: HashMap<PathBuf, Dir>,
+
+    /// The paths scanned from, and the folders seen under them, since the
+    /// cache was last saved.
+    roots: Vec<PathBuf>,
+    seen: HashSet<PathBuf>,
+
+    modified: bool,
+}
+
+impl Cache {
+    pub fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("cramp").join("library"))
+    }
+
+    /// Loads the cache file, starting empty if there isn't a valid one.
+    pub fn load() -> io::Result<Self> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn load_from(path: &Path) -> io::Result<Self> {
+        let mut cache = Self::default();
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(e),
+        };
+
+        let mut lines = bytes.split(|b| *b == b'\n');
+        if lines.next() != Some(HEADER.as_bytes()) {
+            // an older format; it'll be rebuilt
+            return Ok(cache);
+        }
+
+        let mut dirs: Vec<(PathBuf, Dir)> = Vec::new();
+        // whether the entries that follow belong to the last folder in `dirs`
+        let mut valid = false;
+        for line in lines {
+            let fields: Vec<_> = line.split(|b| *b == b'\t').collect();
+            match fields.as_slice() {
+                [b"D", mtime, size, path] => {
+                    valid = false;
+                    if let (Some(stamp), Some(path)) = (parse_stamp(mtime, size), unescape(path)) {
+                        dirs.push((
+                            path.into(),
+                            Dir {
+                                stamp,
+                                entries: Vec::new(),
+                            },
+                        ));
+                        valid = true;
+                    }
+                }
+                [b"F", flags, mtime, size, name, title, artist, album] if valid => {
+                    let (Some((_, dir)), Some(name)) = (dirs.last_mut(), unescape(name)) else {
+                        continue;
+                    };
+                    dir.entries.push(Entry {
+                        name,
+                        dir: flags.contains(&b'd'),
+                        symlink: flags.contains(&b'l'),
+                        stamp: parse_stamp(mtime, size),
+                        audio: if flags.contains(&b'a') {
+                            Some(true)
+                        } else if flags.contains(&b'n') {
+                            Some(false)
+                        } else {
+                            None
+                        },
+                        tags: flags.contains(&b't').then(|| Tags {
+                            title: unescape_tag(title),
+                            artist: unescape_tag(artist),
+                            album: unescape_tag(album),
+                        }),
+                    });
+                }
+                _ => {}
+            }
+        }
+        cache.dirs = dirs.into_iter().collect();
+
+        Ok(cache)
+    }
+
+    /// Notes that a scan is starting from `path`.
+    pub fn scanning(&mut self, path: impl Into<PathBuf>) {
+        self.roots.push(path.into());
+    }
+
+    /// Returns the cached contents of `dir`, if it hasn't changed since.
+    pub fn get(&mut self, dir: &Path, stamp: Stamp) -> Option<Dir> {
+        self.seen.insert(dir.to_path_buf());
+        self.dirs
+            .get(dir)
+            .filter(|cached| cached.stamp == stamp)
+            .cloned()
+    }
+
+    /// Returns the cached tags of the file at `path`, if it hasn't changed
+    /// since they were read.
+    pub fn tags(&self, path: &Path) -> Option<Tags> {
+        let dir = self.dirs.get(path.parent()?)?;
+        let name = path.file_name()?;
+        let entry = dir.entries.iter().find(|entry| entry.name == name)?;
+        let stamp = Stamp::of(path);
+        (stamp.is_some() && entry.stamp == stamp)
+            .then(|| entry.tags.clone())
+            .flatten()
+    }
+
+    pub fn insert(&mut self, dir: PathBuf, contents: Dir) {
+        self.seen.insert(dir.clone());
+        self.dirs.insert(dir, contents);
+        self.modified = true;
+    }
+
+    /// Writes the cache file, forgetting any folders that weren't seen in
+    /// the latest scans (they're gone, or ignored now).
+    pub fn save(&mut self) -> io::Result<()> {
+        match Self::path() {
+            Some(path) => self.save_to(&path),
+            None => Ok(()),
+        }
+    }
+
+    fn save_to(&mut self, path: &Path) -> io::Result<()> {
+        let roots = std::mem::take(&mut self.roots);
+        let seen = std::mem::take(&mut self.seen);
+        let before = self.dirs.len();
+        self.dirs
+            .retain(|dir, _| seen.contains(dir) || !roots.iter().any(|root| dir.starts_with(root)));
+
+        if !self.modified && self.dirs.len() == before {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut out = Vec::new();
+        writeln!(out, "{HEADER}")?;
+        for (dir, contents) in &self.dirs {
+            write!(out, "D\t{}\t", fmt_stamp(Some(contents.stamp)))?;
+            escape(&mut out, dir.as_os_str().as_bytes());
+            out.push(b'\n');
+
+            for entry in &contents.entries {
+                let mut flags = String::new();
+                if entry.dir {
+                    flags.push('d');
+                }
+                if entry.symlink {
+                    flags.push('l');
+                }
+                match entry.audio {
+                    Some(true) => flags.push('a'),
+                    Some(false) => flags.push('n'),
+                    None => {}
+                }
+                if entry.tags.is_some() {
+                    flags.push('t');
+                }
+                if flags.is_empty() {
+                    flags.push('-');
+                }
+
+                write!(out, "F\t{flags}\t{}\t", fmt_stamp(entry.stamp))?;
+                escape(&mut out, entry.name.as_bytes());
+                let tags = entry.tags.clone().unwrap_or_default();
+                for tag in [tags.title, tags.artist, tags.album] {
+                    out.push(b'\t');
+                    escape(&mut out, tag.unwrap_or_default().as_bytes());
+                }
+                out.push(b'\n');
+            }
+        }
+
+        // write it whole, so a crash never leaves half a cache behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, out)?;
+        fs::rename(tmp, path)?;
+
+        self.modified = false;
+        Ok(())
+    }
+}
+
+fn fmt_stamp(stamp: Option<Stamp>) -> String {
+    match stamp {
+        Some(Stamp { mtime, size }) => {
+            format!("{}.{:09}\t{size}", mtime.as_secs(), mtime.subsec_nanos())
+        }
+        None => "-\t-".into(),
+    }
+}
+
+fn parse_stamp(mtime: &[u8], size: &[u8]) -> Option<Stamp> {
+    let mtime = std::str::from_utf8(mtime).ok()?;
+    let (secs, nanos) = mtime.split_once('.')?;
+    Some(Stamp {
+        mtime: Duration::new(secs.parse().ok()?, nanos.parse().ok()?),
+        size: std::str::from_utf8(size).ok()?.parse().ok()?,
+    })
+}
+
+/// Escapes the bytes the format uses as separators.
+fn escape(out: &mut Vec<u8>, bytes: &[u8]) {
+    for b in bytes {
+        match b {
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            _ => out.push(*b),
+        }
+    }
+}
+
+/// Unescapes a tag, where an empty one is missing.
+fn unescape_tag(bytes: &[u8]) -> Option<String> {
+    let tag = unescape(bytes)?.into_string().ok()?;
+    (!tag.is_empty()).then_some(tag)
+}
+
+fn unescape(bytes: &[u8]) -> Option<OsString> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(b) = bytes.next() {
+        if *b == b'\\' {
+            out.push(match bytes.next()? {
+                b't' => b'\t',
+                b'n' => b'\n',
+                b => *b,
+            });
+        } else {
+            out.push(*b);
+        }
+    }
+    Some(OsString::from_vec(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(secs: u64, size: u64) -> Stamp {
+        Stamp {
+            mtime: Duration::new(secs, 5),
+            size,
+        }
+    }
+
+    fn dir(stamp: Stamp, entries: Vec<Entry>) -> Dir {
+        Dir { stamp, entries }
+    }
+
+    fn file(name: &str, audio: Option<bool>, tags: Option<Tags>) -> Entry {
+        Entry {
+            name: name.into(),
+            dir: false,
+            symlink: false,
+            stamp: Some(stamp(1, 2)),
+            audio,
+            tags,
+        }
+    }
+
+    fn cache_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("cramp-cache-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn only_returns_unchanged_dirs() {
+        let mut cache = Cache::default();
+        cache.insert("/music".into(), dir(stamp(10, 4096), Vec::new()));
+
+        assert!(cache.get(Path::new("/music"), stamp(10, 4096)).is_some());
+        assert!(cache.get(Path::new("/music"), stamp(11, 4096)).is_none());
+        assert!(cache.get(Path::new("/music"), stamp(10, 8192)).is_none());
+        assert!(cache.get(Path::new("/other"), stamp(10, 4096)).is_none());
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let path = cache_path("round-trip");
+        let tags = Tags {
+            title: Some("A\ttitle".into()),
+            artist: None,
+            album: Some("Album".into()),
+        };
+
+        let mut cache = Cache::default();
+        cache.insert(
+            "/music/new\nline".into(),
+            dir(
+                stamp(10, 4096),
+                vec![
+                    file("song.mp3", Some(true), Some(tags.clone())),
+                    file("notes", Some(false), None),
+                    file("unknown", None, None),
+                ],
+            ),
+        );
+        cache.save_to(&path).unwrap();
+        let mut loaded = Cache::load_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let dir = loaded
+            .get(Path::new("/music/new\nline"), stamp(10, 4096))
+            .unwrap();
+        let entries: Vec<_> = dir
+            .entries
+            .iter()
+            .map(|e| (e.name.to_str().unwrap(), e.stamp, e.audio, e.tags.clone()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("song.mp3", Some(stamp(1, 2)), Some(true), Some(tags)),
+                ("notes", Some(stamp(1, 2)), Some(false), None),
+                ("unknown", Some(stamp(1, 2)), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn forgets_dirs_gone_from_a_scan() {
+        let path = cache_path("forget");
+        let mut cache = Cache::default();
+        cache.insert("/music/a".into(), dir(stamp(1, 1), Vec::new()));
+        cache.insert("/music/b".into(), dir(stamp(1, 1), Vec::new()));
+        cache.insert("/other".into(), dir(stamp(1, 1), Vec::new()));
+        cache.save_to(&path).unwrap();
+
+        // only `/music/a` is still there when `/music` is scanned again
+        let mut cache = Cache::load_from(&path).unwrap();
+        cache.scanning("/music");
+        cache.get(Path::new("/music/a"), stamp(1, 1));
+        cache.save_to(&path).unwrap();
+
+        let mut cache = Cache::load_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(cache.get(Path::new("/music/a"), stamp(1, 1)).is_some());
+        assert!(cache.get(Path::new("/music/b"), stamp(1, 1)).is_none());
+        assert!(cache.get(Path::new("/other"), stamp(1, 1)).is_some());
+    }
+
+    #[test]
+    fn ignores_other_formats() {
+        let path = cache_path("old");
+        fs::write(&path, "cramp library cache 1\nD\t1.0\t1\t/music\n").unwrap();
+        let mut cache = Cache::load_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(cache.get(Path::new("/music"), stamp(1, 1)).is_none());
+    }
+
+    #[test]
+    fn returns_tags_of_unchanged_files() {
+        let path = cache_path("tagged.mp3");
+        fs::write(&path, b"data").unwrap();
+        let tags = Tags {
+            title: Some("Title".into()),
+            ..Tags::default()
+        };
+        let mut entry = file("", None, Some(tags.clone()));
+        entry.name = path.file_name().unwrap().into();
+        entry.stamp = Stamp::of(&path);
+
+        let mut cache = Cache::default();
+        let parent = path.parent().unwrap().to_path_buf();
+        cache.insert(parent, dir(stamp(1, 1), vec![entry]));
+        assert_eq!(cache.tags(&path), Some(tags));
+
+        fs::write(&path, b"more data").unwrap();
+        let changed = cache.tags(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(changed, None);
+    }
+
+    #[test]
+    fn refreshes_changed_files() {
+        let path = cache_path("song.mp3");
+        fs::write(&path, b"data").unwrap();
+        let mut entry = file("song.mp3", Some(true), Some(Tags::default()));
+
+        entry.refresh(&path);
+        assert_eq!((entry.audio, entry.tags.clone()), (None, None));
+
+        entry.audio = Some(true);
+        entry.refresh(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(entry.audio, Some(true));
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::error::{Error, Severity};
+use crate::{Message, Source};
+
+/// The file extensions of the formats cramp can play.
+const EXTENSIONS: &[&str] = &[
+    "aac", "caf", "flac", "m4a", "m4b", "mka", "mp1", "mp2", "mp3", "mp4", "oga", "ogg", "wav",
+    "wave", "webm",
+];
+
+/// Settings read from `~/.config/cramp/config`.
+///
+/// The file has one `key = value` setting per line, and `#` comments.
+pub struct Config {
+    /// The extensions (lowercase, without the dot) of files loaded as songs
+    /// when walking folders.
+    pub extensions: HashSet<String>,
+
+    /// Whether to follow symlinks when walking folders.
+    pub follow_symlinks: bool,
+
+    /// How many folders deep to walk, if limited.
+    pub max_depth: Option<usize>,
+
+    /// Whether to load hidden (dot) files and folders.
+    pub hidden: bool,
+
+    /// Patterns for the names of files and folders to skip, like the ones in
+    /// a `.crampignore` file.
+    pub ignore: Vec<String>,
+
+    /// Whether to keep a cache of the folders scanned, so unchanged ones
+    /// don't have to be read again on startup.
+    pub cache: bool,
+
+    /// The least severe messages to show.
+    pub messages: Severity,
+
+    /// A file to append every message to, if any.
+    pub log_file: Option<PathBuf>,
+
+    /// Whether to show a desktop notification when a song starts.
+    pub notify: bool,
+
+    /// Whether each notification replaces the last one, instead of stacking.
+    pub notify_replace: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            extensions: EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+
+            follow_symlinks: true,
+            max_depth: None,
+            hidden: false,
+            ignore: Vec::new(),
+            cache: true,
+            messages: Severity::Info,
+            log_file: None,
+            notify: false,
+            notify_replace: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("cramp").join("config"))
+    }
+
+    /// Loads the config file, falling back to the defaults for anything
+    /// missing or invalid.
+    pub fn load() -> (Self, Vec<Message>) {
+        let mut config = Self::default();
+        let mut messages = Vec::new();
+
+        let Some(path) = Self::path() else {
+            return (config, messages);
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return (config, messages),
+            Err(e) => {
+                messages.push(Message::from(Error::io("read", path, e)).source(Source::Config));
+                return (config, messages);
+            }
+        };
+
+        config.parse(&text, &mut messages);
+        (config, messages)
+    }
+
+    /// Applies the settings in a config file, noting any invalid lines.
+    fn parse(&mut self, text: &str, messages: &mut Vec<Message>) {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                messages.push(
+                    Error::Config {
+                        line: i + 1,
+                        reason: "expected `key = value`".into(),
+                    }
+                    .into(),
+                );
+                continue;
+            };
+
+            if let Err(reason) = self.set(key.trim(), value.trim()) {
+                messages.push(
+                    Error::Config {
+                        line: i + 1,
+                        reason,
+                    }
+                    .into(),
+                );
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "extensions" => {
+                self.extensions = list(value)
+                    .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                    .collect();
+            }
+            "follow_symlinks" => self.follow_symlinks = boolean(value)?,
+            "max_depth" => {
+                self.max_depth = match value {
+                    "none" => None,
+                    _ => Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid depth `{value}`"))?,
+                    ),
+                };
+            }
+            "hidden" => self.hidden = boolean(value)?,
+            "ignore" => self.ignore = list(value).map(str::to_string).collect(),
+            "cache" => self.cache = boolean(value)?,
+            "messages" => {
+                self.messages = Severity::from_name(value).ok_or_else(|| {
+                    format!("expected `info`, `warning` or `error`, not `{value}`")
+                })?;
+            }
+            "log_file" => {
+                self.log_file = match (value, value.strip_prefix("~/"), dirs::home_dir()) {
+                    ("none", _, _) => None,
+                    (_, Some(rest), Some(home)) => Some(home.join(rest)),
+                    _ => Some(value.into()),
+                };
+            }
+            "notify" => self.notify = boolean(value)?,
+            "notify_replace" => self.notify_replace = boolean(value)?,
+            _ => return Err(format!("unknown setting `{key}`")),
+        }
+        Ok(())
+    }
+}
+
+fn boolean(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("expected `true` or `false`, not `{value}`")),
+    }
+}
+
+/// Splits a list of values separated by spaces and/or commas.
+fn list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn parse(text: &str) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut messages = Vec::new();
+        config.parse(text, &mut messages);
+        (config, messages.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn parses_settings() {
+        let (config, messages) = parse(
+            "# a comment\n\
+             \n\
+             extensions = .MP3, flac opus\n\
+             follow_symlinks = no\n\
+             max_depth = 3\n\
+             hidden = yes\n\
+             ignore = *.bak, demos\n\
+             messages = warning\n\
+             log_file = /tmp/cramp.log\n\
+             notify = on\n",
+        );
+
+        assert_eq!(messages, Vec::<String>::new());
+        assert_eq!(
+            config.extensions,
+            HashSet::from(["mp3", "flac", "opus"].map(String::from))
+        );
+        assert!(!config.follow_symlinks);
+        assert_eq!(config.max_depth, Some(3));
+        assert!(config.hidden);
+        assert_eq!(config.ignore, ["*.bak", "demos"]);
+        assert_eq!(config.messages, Severity::Warning);
+        assert_eq!(
+            config.log_file.as_deref(),
+            Some(Path::new("/tmp/cramp.log"))
+        );
+        assert!(config.notify);
+        assert!(config.cache);
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        let (config, messages) = parse(
+            "hidden\n\
+             hidden = maybe\n\
+             max_depth = deep\n\
+             messages = loud\n\
+             colour = red\n\
+             max_depth = none\n",
+        );
+
+        assert_eq!(
+            messages,
+            [
+                "config line 1: expected `key = value`",
+                "config line 2: expected `true` or `false`, not `maybe`",
+                "config line 3: invalid depth `deep`",
+                "config line 4: expected `info`, `warning` or `error`, not `loud`",
+                "config line 5: unknown setting `colour`",
+            ]
+        );
+        // invalid settings keep their defaults
+        assert!(!config.hidden);
+        assert_eq!(config.max_depth, None);
+        assert_eq!(config.messages, Severity::Info);
+    }
+}
@@ -0,0 +1,260 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::playlist::{self, Entry, Format};
+use crate::scanner;
+use crate::song::Song;
+use crate::{Message, Source};
+
+/// An `m3u` playlist being edited.
+pub struct Editor {
+    /// Where the playlist is saved, if it ever has been.
+    pub path: Option<PathBuf>,
+    pub title: Option<String>,
+    pub entries: Vec<Entry>,
+
+    /// Whether there are unsaved changes.
+    pub modified: bool,
+
+    pub entries_scroll: usize,
+    pub entry_idx: usize,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            title: None,
+            entries: Vec::new(),
+
+            modified: false,
+
+            entries_scroll: 0,
+            entry_idx: 0,
+        }
+    }
+
+    /// Opens a playlist for editing.
+    ///
+    /// Playlists in other formats are converted, and have to be saved as a new
+    /// `m3u`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Message> {
+        let path = path.as_ref();
+        let playlist = scanner::read_playlist(path)?;
+
+        let is_m3u = matches!(Format::from_path(path), Some(Format::M3u | Format::M3u8));
+        Ok(Self {
+            path: is_m3u.then(|| path.to_path_buf()),
+            title: playlist.title,
+            entries: playlist.entries,
+
+            modified: !is_m3u,
+
+            ..Self::new()
+        })
+    }
+
+    /// Writes the playlist to `path`, which it will be saved to from then on.
+    pub fn save(&mut self, path: PathBuf) -> Message {
+        let home = dirs::home_dir();
+        match playlist::write_m3u(
+            &path,
+            self.title.as_deref(),
+            self.entries.iter().cloned(),
+            home.as_deref(),
+        ) {
+            Ok(n) => {
+                let message = Message::new(format!("saved {n} songs to {}", path.display()))
+                    .source(Source::Playlist);
+                self.path = Some(path);
+                self.modified = false;
+                message
+            }
+            Err(e) => {
+                Message::from(Error::io("save playlist to", path, e)).source(Source::Playlist)
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match (&self.title, &self.path) {
+            (Some(title), _) => title.clone(),
+            (None, Some(path)) => path.display().to_string(),
+            (None, None) => "<new playlist>".into(),
+        }
+    }
+
+    /// Inserts songs after the selected entry, returning how many there were.
+    pub fn add<'a>(&mut self, songs: impl IntoIterator<Item = &'a Song>) -> usize {
+        let at = if self.entries.is_empty() {
+            0
+        } else {
+            self.entry_idx + 1
+        };
+
+        let entries = songs.into_iter().map(|song| Entry {
+            // these tags belong to the playlist the song was loaded from
+            no_shuffle: false,
+            next: None,
+            ..Entry::from(song)
+        });
+        let before = self.entries.len();
+        self.entries.splice(at..at, entries);
+        let added = self.entries.len() - before;
+        self.modified |= added > 0;
+        added
+    }
+
+    pub fn remove(&mut self) {
+        if self.entry_idx < self.entries.len() {
+            self.entries.remove(self.entry_idx);
+            self.modified = true;
+            if self.entry_idx >= self.entries.len() {
+                self.up();
+            }
+        }
+    }
+
+    /// Swaps the selected entry with the one above it.
+    pub fn move_up(&mut self) {
+        if self.entry_idx > 0 && self.entry_idx < self.entries.len() {
+            self.entries.swap(self.entry_idx, self.entry_idx - 1);
+            self.modified = true;
+            self.up();
+        }
+    }
+
+    /// Swaps the selected entry with the one below it.
+    pub fn move_down(&mut self) {
+        if self.entry_idx + 1 < self.entries.len() {
+            self.entries.swap(self.entry_idx, self.entry_idx + 1);
+            self.modified = true;
+            self.down();
+        }
+    }
+
+    /// Toggles `#EXTNOSHUFFLE` on the selected entry.
+    pub fn toggle_no_shuffle(&mut self) {
+        if let Some(entry) = self.entries.get_mut(self.entry_idx) {
+            entry.no_shuffle = !entry.no_shuffle;
+            self.modified = true;
+        }
+    }
+
+    /// Toggles `#EXTNEXT` on the selected entry, pointing at the entry after it.
+    pub fn toggle_next(&mut self) {
+        let next = self.entries.get(self.entry_idx + 1).map(|e| e.path.clone());
+        if let Some(entry) = self.entries.get_mut(self.entry_idx) {
+            entry.next = if entry.next.is_some() { None } else { next };
+            self.modified = true;
+        }
+    }
+
+    pub fn down(&mut self) {
+        if self.entry_idx + 1 < self.entries.len() {
+            self.entry_idx += 1;
+            if self.entry_idx > 5 {
+                self.entries_scroll += 1;
+            }
+        }
+    }
+
+    pub fn up(&mut self) {
+        self.entry_idx = self.entry_idx.saturating_sub(1);
+        if self.entry_idx < self.entries_scroll {
+            self.entries_scroll = self.entry_idx;
+        }
+    }
+}
+
+/// The name to show for an entry or `#EXTNEXT` target.
+pub fn entry_name(path: &Path, title: Option<&str>) -> String {
+    title.map(str::to_string).unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(path: &str) -> Song {
+        Song::new(path).unwrap()
+    }
+
+    fn paths(editor: &Editor) -> Vec<&Path> {
+        editor.entries.iter().map(|e| e.path.as_path()).collect()
+    }
+
+    #[test]
+    fn adds_after_the_selected_entry() {
+        let mut editor = Editor::new();
+        assert_eq!(editor.add(&[song("/a.mp3"), song("/b.mp3")]), 2);
+        assert_eq!(editor.add(&[song("/c.mp3")]), 1);
+        assert_eq!(
+            paths(&editor),
+            ["/a.mp3", "/c.mp3", "/b.mp3"].map(Path::new)
+        );
+        assert!(editor.modified);
+    }
+
+    #[test]
+    fn adding_nothing_changes_nothing() {
+        let mut editor = Editor::new();
+        assert_eq!(editor.add(&[]), 0);
+        assert!(!editor.modified);
+    }
+
+    #[test]
+    fn opens_playlists() {
+        let dir = std::env::temp_dir().join(format!("cramp-editor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let m3u = dir.join("list.m3u");
+        std::fs::write(&m3u, "#PLAYLIST:Mix\na.mp3\n").unwrap();
+        let pls = dir.join("list.pls");
+        std::fs::write(&pls, "[playlist]\nFile1=a.mp3\n").unwrap();
+
+        let opened = Editor::open(&m3u).ok().unwrap();
+        assert_eq!(opened.path.as_deref(), Some(m3u.as_path()));
+        assert_eq!(opened.title.as_deref(), Some("Mix"));
+        assert_eq!(paths(&opened), [dir.join("a.mp3")]);
+        assert!(!opened.modified);
+
+        // other formats are converted, to be saved somewhere new
+        let converted = Editor::open(&pls).ok().unwrap();
+        assert_eq!(converted.path, None);
+        assert_eq!(paths(&converted), [dir.join("a.mp3")]);
+        assert!(converted.modified);
+
+        let missing = Editor::open(dir.join("missing.m3u"));
+        let unknown = Editor::open(dir.join("list.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(missing.is_err());
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn edits_entries() {
+        let mut editor = Editor::new();
+        editor.add(&[song("/a.mp3"), song("/b.mp3"), song("/c.mp3")]);
+
+        editor.move_down();
+        assert_eq!(
+            paths(&editor),
+            ["/b.mp3", "/a.mp3", "/c.mp3"].map(Path::new)
+        );
+        assert_eq!(editor.entry_idx, 1);
+
+        editor.toggle_next();
+        assert_eq!(editor.entries[1].next.as_deref(), Some(Path::new("/c.mp3")));
+        editor.toggle_next();
+        assert_eq!(editor.entries[1].next, None);
+
+        editor.down();
+        editor.remove();
+        assert_eq!(paths(&editor), ["/b.mp3", "/a.mp3"].map(Path::new));
+        assert_eq!(editor.entry_idx, 1);
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::PathBuf;
+use std::{fmt, io};
+
+use kittyaudio::KaError;
+
+/// How much a message matters, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "info" => Some(Self::Info),
+            "warning" => Some(Self::Warning),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Everything that can go wrong, kept around (in a [`crate::Message`]) so it
+/// can be told apart after it's been shown.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file or folder failed.
+    Io {
+        /// What was being done, e.g. "read" or "save".
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+
+    /// A song couldn't be decoded.
+    Decode {
+        /// What was being done, e.g. "play" or "load".
+        action: &'static str,
+        path: PathBuf,
+        source: KaError,
+    },
+
+    /// A playlist couldn't be understood.
+    Playlist { path: PathBuf, reason: String },
+
+    /// A path can't be a song (e.g. it's `/`).
+    NotASong(PathBuf),
+
+    /// A line in the config file is invalid.
+    Config { line: usize, reason: String },
+
+    /// Watching the library for changes failed, for `dirs` folders (or the
+    /// whole library if it's 0).
+    Watch { dirs: usize, source: notify::Error },
+
+    /// Talking to D-Bus failed.
+    Dbus(zbus::Error),
+}
+
+impl Error {
+    pub fn io(action: &'static str, path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Self::Io {
+            action,
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::Io { .. } | Self::Decode { .. } | Self::Dbus(_) => Severity::Error,
+            Self::Playlist { .. }
+            | Self::NotASong(_)
+            | Self::Config { .. }
+            | Self::Watch { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io {
+                action,
+                path,
+                source,
+            } => write!(f, "failed to {action} {}: {source}", path.display()),
+            Self::Decode {
+                action,
+                path,
+                source,
+            } => write!(f, "failed to {action} song {}: {source}", path.display()),
+            Self::Playlist { path, reason } => write!(f, "{}: {reason}", path.display()),
+            Self::NotASong(path) => write!(f, "not a song: {}", path.display()),
+            Self::Config { line, reason } => write!(f, "config line {line}: {reason}"),
+            Self::Watch { dirs: 0, source } => write!(f, "failed to watch library: {source}"),
+            Self::Watch { dirs, source } => {
+                write!(f, "failed to watch {dirs} directories: {source}")
+            }
+            Self::Dbus(e) => write!(f, "dbus error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Decode { source, .. } => Some(source),
+            Self::Watch { source, .. } => Some(source),
+            Self::Dbus(e) => Some(e),
+            Self::Playlist { .. } | Self::NotASong(_) | Self::Config { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::*;
+
+    #[test]
+    fn names_severities() {
+        for severity in [Severity::Info, Severity::Warning, Severity::Error] {
+            assert_eq!(Severity::from_name(severity.name()), Some(severity));
+        }
+        assert_eq!(Severity::from_name("loud"), None);
+        assert!(Severity::Info < Severity::Warning && Severity::Warning < Severity::Error);
+    }
+
+    #[test]
+    fn describes_errors() {
+        let io = Error::io(
+            "read",
+            "/music/a.mp3",
+            io::Error::new(io::ErrorKind::NotFound, "gone"),
+        );
+        assert_eq!(io.to_string(), "failed to read /music/a.mp3: gone");
+        assert_eq!(io.severity(), Severity::Error);
+        assert_eq!(io.source().unwrap().to_string(), "gone");
+
+        let config = Error::Config {
+            line: 3,
+            reason: "unknown setting `colour`".into(),
+        };
+        assert_eq!(
+            config.to_string(),
+            "config line 3: unknown setting `colour`"
+        );
+        assert_eq!(config.severity(), Severity::Warning);
+        assert!(config.source().is_none());
+
+        let not_a_song = Error::NotASong("/".into());
+        assert_eq!(not_a_song.to_string(), "not a song: /");
+    }
+}
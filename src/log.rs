@@ -0,0 +1,132 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use circular_buffer::CircularBuffer;
+
+use crate::error::{Error, Severity};
+use crate::Message;
+
+/// How many messages the log keeps; older ones are dropped.
+const LEN: usize = 256;
+
+/// A message, and when it was sent.
+pub struct Record {
+    pub time: DateTime<Local>,
+    pub message: Message,
+}
+
+/// Every message sent, most recent last, optionally mirrored to a file.
+pub struct Log {
+    records: Box<CircularBuffer<LEN, Record>>,
+    file: Option<(File, PathBuf)>,
+}
+
+impl Log {
+    /// Creates a log, appending to the file at `path` if there is one.
+    pub fn new(path: Option<&Path>) -> Self {
+        let mut log = Self {
+            records: CircularBuffer::boxed(),
+            file: None,
+        };
+
+        if let Some(path) = path {
+            match OpenOptions::new().create(true).append(true).open(path) {
+                Ok(file) => log.file = Some((file, path.to_path_buf())),
+                Err(e) => log.push(Error::io("open log file", path, e).into()),
+            }
+        }
+        log
+    }
+
+    pub fn push(&mut self, message: Message) {
+        let record = Record {
+            time: Local::now(),
+            message,
+        };
+
+        if let Some((file, path)) = &mut self.file {
+            // details go on indented lines after the message
+            let line = format!(
+                "{} {}\n",
+                record.time.format("%Y-%m-%d %H:%M:%S"),
+                record.message.detail().replace('\n', "\n    ")
+            );
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                // stop mirroring, rather than failing on every message
+                let path = std::mem::take(path);
+                self.file = None;
+                self.push(Error::io("write log file", path, e).into());
+            }
+        }
+
+        self.records.push_back(record);
+    }
+
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &Record> {
+        self.records.iter()
+    }
+
+    /// The messages at least as severe as `min`, which are the ones shown.
+    ///
+    /// Less severe ones are still kept, so they show up again if `min` is
+    /// lowered.
+    pub fn shown(&self, min: Severity) -> impl DoubleEndedIterator<Item = &Record> {
+        self.records()
+            .filter(move |record| record.message.level >= min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts<'a>(records: impl Iterator<Item = &'a Record>) -> Vec<&'a str> {
+        records.map(|record| &*record.message).collect()
+    }
+
+    #[test]
+    fn filters_only_when_shown() {
+        let mut log = Log::new(None);
+        log.push(Message::new("info"));
+        log.push(Message::new("warning").level(Severity::Warning));
+        log.push(Message::new("error").level(Severity::Error));
+
+        assert_eq!(texts(log.records()), ["info", "warning", "error"]);
+        assert_eq!(texts(log.shown(Severity::Warning)), ["warning", "error"]);
+        assert_eq!(
+            texts(log.shown(Severity::Info)),
+            ["info", "warning", "error"]
+        );
+    }
+
+    #[test]
+    fn keeps_the_latest_messages() {
+        let mut log = Log::new(None);
+        for i in 0..LEN + 10 {
+            log.push(Message::new(i.to_string()));
+        }
+
+        let records = texts(log.records());
+        assert_eq!(records.len(), LEN);
+        assert_eq!(records[0], "10");
+        assert_eq!(records.last(), Some(&&*(LEN + 9).to_string()));
+    }
+
+    #[test]
+    fn mirrors_to_a_file() {
+        let path = std::env::temp_dir().join(format!("cramp-log-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let message = || Message::new("hello").level(Severity::Warning);
+        let mut log = Log::new(Some(&path));
+        log.push(message());
+        drop(log);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text.lines().count(), 1);
+        assert!(text.ends_with(&format!(" {}\n", message().detail())));
+    }
+}
@@ -1,29 +1,156 @@
-use std::fmt;
+use std::borrow::Cow;
+use std::error::Error as _;
+use std::fmt::{self, Write};
 use std::ops::Deref;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 mod app;
+mod browser;
+mod cache;
+mod config;
+mod editor;
+mod error;
+mod log;
+mod notifier;
 mod player;
+mod playlist;
 mod queue;
+mod scanner;
 mod song;
+mod tags;
 mod ui;
+mod watcher;
 
+use app::control::Control;
 use app::{App, Effect};
+use error::{Error, Severity};
 use mpris_server::Server;
+use notifier::Notifier;
+use song::Song;
 
-pub enum Message {
-    Static(&'static str),
-    Dynamic(String),
+/// What a message is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    App,
+    Player,
+    Library,
+    Playlist,
+    Config,
+    Dbus,
+}
+
+impl Source {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::App => "app",
+            Self::Player => "player",
+            Self::Library => "library",
+            Self::Playlist => "playlist",
+            Self::Config => "config",
+            Self::Dbus => "dbus",
+        }
+    }
+}
+
+/// A notification for the user: a line of text, how much it matters, what
+/// it's about, and the song it concerns, if any.
+pub struct Message {
+    /// Always a single line.
+    text: Cow<'static, str>,
+
+    pub level: Severity,
+    pub source: Source,
+    pub song: Option<Box<Song>>,
+
+    /// What went wrong, if anything.
+    pub error: Option<Error>,
 }
 
 impl Message {
     pub fn new(s: impl ToString) -> Self {
-        Self::Dynamic(s.to_string())
+        Self::with_text(Cow::Owned(s.to_string()))
     }
 
     pub fn stc(s: &'static str) -> Self {
-        Self::Static(s)
+        Self::with_text(Cow::Borrowed(s))
+    }
+
+    fn with_text(text: Cow<'static, str>) -> Self {
+        let text = if text.contains('\n') {
+            Cow::Owned(text.split_whitespace().collect::<Vec<_>>().join(" "))
+        } else {
+            text
+        };
+
+        Self {
+            text,
+            level: Severity::Info,
+            source: Source::App,
+            song: None,
+            error: None,
+        }
+    }
+
+    pub fn level(mut self, level: Severity) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn source(mut self, source: Source) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn song(mut self, song: &Song) -> Self {
+        self.song = Some(Box::new(song.clone()));
+        self
+    }
+
+    /// The message and everything known about it, over several lines.
+    pub fn detail(&self) -> String {
+        let mut detail = format!("{} ({}): {self}", self.level.name(), self.source.name());
+
+        if let Some(song) = &self.song {
+            let _ = write!(detail, "\nsong: {}", song.name);
+            if let Some(artist) = &song.artist {
+                let _ = write!(detail, " by {artist}");
+            }
+            if let Some(album) = &song.album {
+                let _ = write!(detail, " on {album}");
+            }
+            let _ = write!(detail, "\nfile: {}", song.path.display());
+        }
+
+        // the error's own cause is already part of the text
+        let mut cause = self
+            .error
+            .as_ref()
+            .and_then(|e| e.source())
+            .and_then(|e| e.source());
+        while let Some(e) = cause {
+            let _ = write!(detail, "\ncaused by: {e}");
+            cause = e.source();
+        }
+
+        detail
+    }
+}
+
+impl From<Error> for Message {
+    fn from(e: Error) -> Self {
+        let source = match e {
+            Error::Io { .. } => Source::App,
+            Error::Decode { .. } => Source::Player,
+            Error::Playlist { .. } => Source::Playlist,
+            Error::NotASong(_) | Error::Watch { .. } => Source::Library,
+            Error::Config { .. } => Source::Config,
+            Error::Dbus(_) => Source::Dbus,
+        };
+
+        let mut message = Self::new(&e).level(e.severity()).source(source);
+        message.error = Some(e);
+        message
     }
 }
 
@@ -31,23 +158,13 @@ impl Deref for Message {
     type Target = str;
 
     fn deref(&self) -> &str {
-        match self {
-            Self::Static(s) => s,
-            Self::Dynamic(s) => s,
-        }
+        &self.text
     }
 }
 
 impl fmt::Display for Message {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(
-            f,
-            "{}",
-            match self {
-                Self::Static(s) => *s,
-                Self::Dynamic(s) => s,
-            }
-        )
+        f.write_str(&self.text)
     }
 }
 
@@ -69,20 +186,47 @@ async fn main() {
     );
 
     let app = server.imp();
+    if let Err(e) = server
+        .connection()
+        .object_server()
+        .at("/com/cramp/Library", Control(server.clone()))
+        .await
+    {
+        app.add_message(Message::from(Error::Dbus(e))).await;
+    }
+
+    let mut notifier = None;
+    if app.config.notify {
+        match Notifier::new(server.connection(), app.config.notify_replace).await {
+            Ok(n) => notifier = Some(n),
+            Err(e) => app.add_message(e.into()).await,
+        }
+    }
+
     loop {
         app.poll().await;
-        for effect in app.effects.lock().await.drain(..) {
+        // taken out first, as handling them locks the app again
+        let effects = std::mem::take(&mut *app.effects.lock().await);
+        for effect in effects {
             match effect {
                 Effect::Signal(s) => {
                     if let Err(e) = server.emit(s).await {
-                        app.add_message(Message::new(format!("dbus error: {e}")))
-                            .await;
+                        app.add_message(Message::from(Error::Dbus(e))).await;
                     }
                 }
                 Effect::Changed(c) => {
                     if let Err(e) = server.properties_changed(c).await {
-                        app.add_message(Message::new(format!("dbus error: {e}")))
-                            .await;
+                        app.add_message(Message::from(Error::Dbus(e))).await;
+                    }
+                }
+                Effect::Notify(song) => {
+                    let Some(n) = &mut notifier else {
+                        continue;
+                    };
+                    if let Err(e) = n.notify(&song).await {
+                        // likely no notification server, so don't fail every song
+                        notifier = None;
+                        app.add_message(Message::from(e).song(&song)).await;
                     }
                 }
             }
@@ -93,3 +237,68 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn keeps_messages_on_one_line() {
+        let message = Message::new("failed\n  to read:\tthis");
+        assert_eq!(message.to_string(), "failed to read: this");
+        assert_eq!(&*Message::stc("one line"), "one line");
+    }
+
+    #[test]
+    fn takes_after_errors() {
+        let message = Message::from(Error::Config {
+            line: 2,
+            reason: "oops".into(),
+        });
+        assert_eq!(message.to_string(), "config line 2: oops");
+        assert_eq!(message.level, Severity::Warning);
+        assert_eq!(message.source, Source::Config);
+        assert!(message.error.is_some());
+    }
+
+    /// An error with a cause of its own.
+    #[derive(Debug)]
+    struct Outer(io::Error);
+
+    impl fmt::Display for Outer {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("bad sector")
+        }
+    }
+
+    impl std::error::Error for Outer {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn details_the_song_and_cause() {
+        let song = Song::new("/music/song.mp3")
+            .unwrap()
+            .artist(Some("Band".into()))
+            .album(Some("Album".into()));
+        let cause = Outer(io::Error::other("disk on fire"));
+        let error = Error::io("read", "/music/song.mp3", io::Error::other(cause));
+        let message = Message::from(error).song(&song);
+
+        assert_eq!(
+            message.detail(),
+            "error (app): failed to read /music/song.mp3: bad sector\n\
+             song: song by Band on Album\n\
+             file: /music/song.mp3\n\
+             caused by: disk on fire"
+        );
+        assert_eq!(
+            Message::new("hi").source(Source::Dbus).detail(),
+            "info (dbus): hi"
+        );
+    }
+}
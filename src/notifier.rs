@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zbus::zvariant::Value;
+use zbus::Connection;
+
+use crate::error::Error;
+use crate::song::Song;
+
+/// The names (without the extension) of cover art files, in order of
+/// preference.
+const COVERS: &[&str] = &["cover", "folder", "front", "album"];
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+/// Shows a desktop notification for each song played.
+pub struct Notifier {
+    proxy: NotificationsProxy<'static>,
+
+    /// Whether each notification replaces the last one, instead of stacking.
+    replace: bool,
+    /// The ID of the last notification sent, if any.
+    last: u32,
+}
+
+impl Notifier {
+    /// Creates a notifier talking to the notification server on `connection`
+    /// (normally the session bus).
+    pub async fn new(connection: &Connection, replace: bool) -> Result<Self, Error> {
+        let proxy = NotificationsProxy::new(connection)
+            .await
+            .map_err(Error::Dbus)?;
+
+        Ok(Self {
+            proxy,
+            replace,
+            last: 0,
+        })
+    }
+
+    /// Notifies that `song` started playing, with its title, artist and album,
+    /// and the cover art in its folder if there is any.
+    pub async fn notify(&mut self, song: &Song) -> Result<(), Error> {
+        let body = match (&song.artist, &song.album) {
+            (Some(artist), Some(album)) => format!("{artist} — {album}"),
+            (Some(artist), None) => artist.clone(),
+            (None, Some(album)) => album.clone(),
+            (None, None) => String::new(),
+        };
+
+        let mut hints = HashMap::new();
+        let cover = song
+            .path
+            .parent()
+            .and_then(cover)
+            .map(|path| file_uri(&path));
+        if let Some(cover) = &cover {
+            hints.insert("image-path", Value::from(cover.as_str()));
+        }
+
+        let replaces = if self.replace { self.last } else { 0 };
+        self.last = self
+            .proxy
+            .notify(
+                "cramp",
+                replaces,
+                "",
+                &markup_escape(&song.name),
+                &markup_escape(&body),
+                &[],
+                hints,
+                -1,
+            )
+            .await
+            .map_err(Error::Dbus)?;
+
+        Ok(())
+    }
+}
+
+/// Finds the cover art in a folder, e.g. `cover.jpg`.
+fn cover(dir: &Path) -> Option<PathBuf> {
+    let mut images: Vec<_> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+            if !matches!(ext.as_str(), "jpg" | "jpeg" | "png") {
+                return None;
+            }
+
+            let stem = path.file_stem()?.to_str()?.to_ascii_lowercase();
+            let rank = COVERS.iter().position(|name| *name == stem)?;
+            Some((rank, path))
+        })
+        .collect();
+
+    images.sort();
+    images.into_iter().next().map(|(_, path)| path)
+}
+
+fn file_uri(path: &Path) -> String {
+    // a relative path means nothing to the notification server
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.to_string_lossy();
+    let path: Vec<_> = path.split('/').map(urlencoding::encode).collect();
+    format!("file://{}", path.join("/"))
+}
+
+/// Escapes the characters notification servers might take for markup.
+fn markup_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::{Arc, Mutex};
+
+    use zbus::zvariant::OwnedValue;
+
+    use super::*;
+
+    /// What a notification server was sent.
+    #[derive(Debug, PartialEq)]
+    struct Sent {
+        replaces_id: u32,
+        summary: String,
+        body: String,
+        image_path: Option<String>,
+    }
+
+    /// A notification server that keeps what it's sent.
+    struct Stub(Arc<Mutex<Vec<Sent>>>);
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl Stub {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: String,
+            replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let mut sent = self.0.lock().unwrap();
+            sent.push(Sent {
+                replaces_id,
+                summary,
+                body,
+                image_path: hints
+                    .get("image-path")
+                    .and_then(|value| String::try_from(value.try_clone().ok()?).ok()),
+            });
+            sent.len() as u32
+        }
+    }
+
+    /// A private session bus with a stub notification server on it,
+    /// stopped when dropped.
+    struct Bus {
+        daemon: Child,
+        stub: Option<Connection>,
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// Connects to a stub notification server on a private bus, returning
+    /// what it's sent, or `None` if there's no `dbus-daemon` to run one.
+    async fn connect() -> Option<(Bus, Connection, Arc<Mutex<Vec<Sent>>>)> {
+        let daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut bus = match daemon {
+            Ok(daemon) => Bus { daemon, stub: None },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!("skipped: no dbus-daemon");
+                return None;
+            }
+            Err(e) => panic!("can't start dbus-daemon: {e}"),
+        };
+        let mut address = String::new();
+        let stdout = bus.daemon.stdout.take().unwrap();
+        BufReader::new(stdout).read_line(&mut address).unwrap();
+        let address = address.trim();
+
+        let sent = Arc::default();
+        let stub = zbus::connection::Builder::address(address)
+            .unwrap()
+            .name("org.freedesktop.Notifications")
+            .unwrap()
+            .serve_at("/org/freedesktop/Notifications", Stub(Arc::clone(&sent)))
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        bus.stub = Some(stub);
+        let client = zbus::connection::Builder::address(address)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        Some((bus, client, sent))
+    }
+
+    fn song(dir: &Path) -> Song {
+        Song::new(dir.join("song.mp3"))
+            .unwrap()
+            .artist(Some("Band & Co".into()))
+            .album(Some("Album".into()))
+    }
+
+    #[async_std::test]
+    async fn sends_song_details() {
+        let Some((_bus, client, sent)) = connect().await else {
+            return;
+        };
+
+        let dir = std::env::temp_dir().join(format!("cramp-notifier-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Cover.JPG"), b"").unwrap();
+        fs::write(dir.join("back.jpg"), b"").unwrap();
+        let mut notifier = Notifier::new(&client, false).await.unwrap();
+        notifier.notify(&song(&dir)).await.unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            sent.lock().unwrap()[0],
+            Sent {
+                replaces_id: 0,
+                summary: "song".into(),
+                body: "Band &amp; Co — Album".into(),
+                image_path: Some(file_uri(&dir.join("Cover.JPG"))),
+            }
+        );
+    }
+
+    #[async_std::test]
+    async fn replaces_the_last_notification() {
+        let dir = Path::new("/nonexistent");
+        for (replace, second) in [(false, 0), (true, 1)] {
+            let Some((_bus, client, sent)) = connect().await else {
+                return;
+            };
+            let mut notifier = Notifier::new(&client, replace).await.unwrap();
+            notifier.notify(&song(dir)).await.unwrap();
+            notifier.notify(&song(dir)).await.unwrap();
+
+            let sent = sent.lock().unwrap();
+            assert_eq!(sent[0].replaces_id, 0);
+            assert_eq!(sent[0].image_path, None);
+            assert_eq!(sent[1].replaces_id, second);
+        }
+    }
+
+    #[test]
+    fn makes_file_uris() {
+        assert_eq!(
+            file_uri(Path::new("/music/a b/cover #1.jpg")),
+            "file:///music/a%20b/cover%20%231.jpg"
+        );
+        let relative = file_uri(Path::new("cover.jpg"));
+        assert!(relative.starts_with("file:///"), "{relative}");
+        assert!(relative.ends_with("/cover.jpg"), "{relative}");
+    }
+}
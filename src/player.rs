@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use kittyaudio::{KaError, Mixer, Sound, SoundHandle};
 
 use crate::song::Song;
@@ -5,7 +7,14 @@ use crate::song::Song;
 pub struct Player {
     mixer: Mixer,
     current: Option<SoundHandle>,
-    next: Option<Sound>,
+
+    /// The file decoded last, so songs sharing it (e.g. from a cue sheet)
+    /// and the preloaded next song don't have to be decoded again.
+    decoded: Option<(PathBuf, Sound)>,
+
+    /// Where in the file the current song starts and ends, in seconds.
+    start: f64,
+    end: Option<f64>,
 }
 
 impl Player {
@@ -15,7 +24,10 @@ impl Player {
         Self {
             mixer,
             current: None,
-            next: None,
+            decoded: None,
+
+            start: 0.0,
+            end: None,
         }
     }
 
@@ -24,31 +36,48 @@ impl Player {
     }
 
     pub fn finished(&self) -> bool {
-        self.current.as_ref().map(|c| c.finished()).unwrap_or(true)
+        self.current
+            .as_ref()
+            .map(|c| {
+                c.finished()
+                    || self
+                        .end
+                        .is_some_and(|end| c.index() as f64 / c.sample_rate() as f64 >= end)
+            })
+            .unwrap_or(true)
     }
 
     /// Returns the time elapsed and total time, in that order, of the current song (if any).
     pub fn time_info(&self) -> Option<(f64, f64)> {
         self.current.as_ref().map(|c| {
+            let end = self.end.unwrap_or_else(|| c.duration_seconds());
             (
-                c.index() as f64 / c.sample_rate() as f64,
-                c.duration_seconds(),
+                c.index() as f64 / c.sample_rate() as f64 - self.start,
+                end - self.start,
             )
         })
     }
 
     pub fn seek_by(&mut self, seconds: f64) {
         if let Some(c) = &self.current {
-            c.seek_by(seconds);
+            let position = c.index() as f64 / c.sample_rate() as f64;
+            c.seek_to(self.clamp(c, position + seconds));
         }
     }
 
     pub fn seek_to(&mut self, seconds: f64) {
         if let Some(c) = &self.current {
-            c.seek_to(seconds);
+            c.seek_to(self.clamp(c, self.start + seconds));
         }
     }
 
+    /// Keeps a position in the file within the current song, so seeking
+    /// never plays into a neighbouring one.
+    fn clamp(&self, current: &SoundHandle, position: f64) -> f64 {
+        let end = self.end.unwrap_or_else(|| current.duration_seconds());
+        position.min(end).max(self.start)
+    }
+
     pub fn resume(&mut self) {
         if let Some(current) = &self.current {
             current.resume();
@@ -71,18 +100,44 @@ impl Player {
     pub fn stop(&mut self) {
         if let Some(song) = &self.current {
             song.reset();
+            song.seek_to(self.start);
             song.pause();
         }
     }
 
     pub fn play(&mut self, song: &Song) -> Result<(), KaError> {
+        // a song sharing its file wouldn't have finished, so end it properly
+        if self.end.is_some() {
+            self.end();
+        }
         self.stop();
-        self.current = Some(self.mixer.play(song.load()?));
+
+        let sound = self.load(song)?;
+        let handle = self.mixer.play(sound);
+        self.start = song.start.unwrap_or(0.0);
+        self.end = song.end;
+        if self.start > 0.0 {
+            handle.seek_to(self.start);
+        }
+
+        self.current = Some(handle);
         Ok(())
     }
 
     pub fn load_next(&mut self, song: &Song) -> Result<(), KaError> {
-        self.next = Some(song.load()?);
+        self.load(song)?;
         Ok(())
     }
+
+    fn load(&mut self, song: &Song) -> Result<Sound, KaError> {
+        if let Some((path, sound)) = &self.decoded {
+            if *path == song.path {
+                return Ok(sound.clone());
+            }
+        }
+
+        let sound = song.load()?;
+        self.decoded = Some((song.path.clone(), sound.clone()));
+        Ok(sound)
+    }
 }
@@ -0,0 +1,848 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::error::Error;
+use crate::song::Song;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    M3u,
+    M3u8,
+    Pls,
+    Xspf,
+    Cue,
+}
+
+impl Format {
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_string_lossy().to_lowercase();
+        Some(match ext.as_str() {
+            "m3u" | "m3u4" => Self::M3u,
+            "m3u8" => Self::M3u8,
+            "pls" => Self::Pls,
+            "xspf" => Self::Xspf,
+            "cue" => Self::Cue,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct Playlist {
+    pub title: Option<String>,
+    pub entries: Vec<Entry>,
+
+    /// The number of entries that didn't point to a local file.
+    pub skipped: usize,
+}
+
+#[derive(Default, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub next: Option<PathBuf>,
+    pub no_shuffle: bool,
+
+    /// Where in the file the song starts and ends, in seconds.
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+impl From<&Song> for Entry {
+    fn from(song: &Song) -> Self {
+        Self {
+            path: song.path.clone(),
+            title: Some(song.name.clone()),
+            artist: song.artist.clone(),
+            album: song.album.clone(),
+            next: song.next.clone(),
+            no_shuffle: song.no_shuffle,
+            start: song.start,
+            end: song.end,
+        }
+    }
+}
+
+impl Entry {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            ..Default::default()
+        }
+    }
+
+    pub fn into_song(self) -> Result<Song, Error> {
+        Ok(Song::new(self.path)?
+            .next(self.next)
+            .no_shuffle(self.no_shuffle)
+            .title(self.title)
+            .artist(self.artist)
+            .album(self.album)
+            .bounds(self.start, self.end))
+    }
+}
+
+/// Reads a playlist file.
+///
+/// `m3u8` is always UTF-8; the other formats fall back to Latin-1 if the file
+/// isn't valid UTF-8.
+pub fn read(path: impl AsRef<Path>, format: Format) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) if format == Format::M3u8 => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        Err(e) => e.into_bytes().into_iter().map(char::from).collect(),
+    })
+}
+
+/// Writes entries to an `m3u` playlist, such that [`parse`] reads them back
+/// the same.
+///
+/// Paths under `home` are written as `~/` paths.
+pub fn write_m3u(
+    path: impl AsRef<Path>,
+    title: Option<&str>,
+    entries: impl IntoIterator<Item = Entry>,
+    home: Option<&Path>,
+) -> io::Result<usize> {
+    let mut out = String::from("#EXTM3U\n");
+    if let Some(title) = title {
+        let _ = writeln!(out, "#PLAYLIST:{title}");
+    }
+
+    let mut written = 0;
+    for entry in entries {
+        let stem = entry.path.file_stem().map(|s| s.to_string_lossy());
+        let title = entry.title.as_deref().or(stem.as_deref()).unwrap_or("");
+        if entry.artist.is_some() || stem.as_deref() != Some(title) {
+            out.push_str("#EXTINF:-1,");
+            match &entry.artist {
+                Some(artist) => {
+                    let _ = write!(out, "{artist} - ");
+                }
+                // or the first part of the title would be read as the artist
+                None if title.contains(" - ") || title.starts_with("- ") => {
+                    out.push_str(" - ");
+                }
+                None => {}
+            }
+            out.push_str(title);
+            out.push('\n');
+        }
+
+        if entry.no_shuffle {
+            out.push_str("#EXTNOSHUFFLE\n");
+        }
+
+        if let Some(next) = &entry.next {
+            let _ = writeln!(out, "#EXTNEXT:{}", unresolve(next, home));
+        }
+
+        if entry.start.is_some() || entry.end.is_some() {
+            let bound = |secs: Option<f64>| secs.map(|s| s.to_string()).unwrap_or_default();
+            let _ = writeln!(out, "#EXTRANGE:{}-{}", bound(entry.start), bound(entry.end));
+        }
+
+        out.push_str(&unresolve(&entry.path, home));
+        out.push('\n');
+        written += 1;
+    }
+
+    fs::write(path, out)?;
+    Ok(written)
+}
+
+/// The inverse of [`resolve`]: makes `path` absolute, or relative to `home` if
+/// it's inside it.
+fn unresolve(path: &Path, home: Option<&Path>) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Parses a playlist whose relative paths are relative to `base`.
+pub fn parse(format: Format, text: &str, base: &Path, home: &Path) -> Playlist {
+    match format {
+        Format::M3u | Format::M3u8 => parse_m3u(text, base, home),
+        Format::Pls => parse_pls(text, base, home),
+        Format::Xspf => parse_xspf(text, base, home),
+        Format::Cue => parse_cue(text, base, home),
+    }
+}
+
+fn parse_m3u(text: &str, base: &Path, home: &Path) -> Playlist {
+    let mut playlist = Playlist::default();
+    let mut album = None;
+    let mut album_artist = None;
+
+    let mut next = None;
+    let mut no_shuffle = false;
+    let mut bounds = None;
+    let mut info = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(ext) = line.strip_prefix('#') {
+            if ext.trim() == "EXTNOSHUFFLE" {
+                no_shuffle = true;
+            } else if let Some(path) = ext.strip_prefix("EXTNEXT:") {
+                next = resolve(path, base, home);
+            } else if let Some(range) = ext.strip_prefix("EXTRANGE:") {
+                bounds = parse_range(range);
+            } else if let Some(ext) = ext.strip_prefix("EXTINF:") {
+                info = parse_extinf(ext);
+            } else if let Some(name) = ext.strip_prefix("PLAYLIST:") {
+                playlist.title = Some(name.trim().to_string());
+            } else if let Some(name) = ext.strip_prefix("EXTALB:") {
+                album = Some(name.trim().to_string());
+            } else if let Some(name) = ext.strip_prefix("EXTART:") {
+                album_artist = Some(name.trim().to_string());
+            }
+        } else {
+            let (artist, title) = info.take().unzip();
+            let Some(path) = resolve(line, base, home) else {
+                playlist.skipped += 1;
+                next = None;
+                no_shuffle = false;
+                bounds = None;
+                continue;
+            };
+
+            let (start, end) = bounds.take().unwrap_or_default();
+            playlist.entries.push(Entry {
+                title,
+                artist: artist.flatten().or_else(|| album_artist.clone()),
+                album: album.clone(),
+                next: next.take(),
+                no_shuffle,
+                start,
+                end,
+                ..Entry::new(path)
+            });
+
+            no_shuffle = false;
+        }
+    }
+
+    playlist
+}
+
+fn parse_pls(text: &str, base: &Path, home: &Path) -> Playlist {
+    let mut playlist = Playlist::default();
+
+    let mut entries = BTreeMap::<usize, (Option<&str>, Option<&str>)>::new();
+    for line in text.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        if let Some(n) = key.strip_prefix("file").and_then(|n| n.parse().ok()) {
+            entries.entry(n).or_default().0 = Some(value);
+        } else if let Some(n) = key.strip_prefix("title").and_then(|n| n.parse().ok()) {
+            entries.entry(n).or_default().1 = Some(value);
+        } else if key == "x-title" {
+            playlist.title = Some(value.to_string());
+        }
+    }
+
+    for (file, title) in entries.into_values() {
+        let Some(path) = file.and_then(|file| resolve(file, base, home)) else {
+            playlist.skipped += 1;
+            continue;
+        };
+
+        playlist.entries.push(Entry {
+            title: title.filter(|t| !t.is_empty()).map(str::to_string),
+            ..Entry::new(path)
+        });
+    }
+
+    playlist
+}
+
+fn parse_xspf(text: &str, base: &Path, home: &Path) -> Playlist {
+    let mut playlist = Playlist::default();
+
+    let (head, tracks) = match text.find("<trackList") {
+        Some(idx) => text.split_at(idx),
+        None => (text, ""),
+    };
+    playlist.title = element(head, "title").map(unescape);
+
+    let mut rest = tracks;
+    while let Some((track, after)) = next_element(rest, "track") {
+        rest = after;
+
+        let location = element(track, "location").map(unescape);
+        let Some(path) = location.and_then(|l| resolve_uri(&l, base, home)) else {
+            playlist.skipped += 1;
+            continue;
+        };
+
+        playlist.entries.push(Entry {
+            title: element(track, "title").map(unescape),
+            artist: element(track, "creator").map(unescape),
+            album: element(track, "album").map(unescape),
+            ..Entry::new(path)
+        });
+    }
+
+    playlist
+}
+
+fn parse_cue(text: &str, base: &Path, home: &Path) -> Playlist {
+    let mut playlist = Playlist::default();
+    let mut performer = None;
+
+    let mut file = None;
+    // each track, and where its pregap (if any) starts
+    let mut tracks: Vec<(Entry, Option<f64>)> = Vec::new();
+    let mut in_track = false;
+    for line in text.lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command.to_ascii_uppercase().as_str() {
+            "TITLE" if in_track => {
+                if let Some((track, _)) = tracks.last_mut() {
+                    track.title = Some(unquote(rest).to_string());
+                }
+            }
+            "TITLE" => playlist.title = Some(unquote(rest).to_string()),
+            "PERFORMER" if in_track => {
+                if let Some((track, _)) = tracks.last_mut() {
+                    track.artist = Some(unquote(rest).to_string());
+                }
+            }
+            "PERFORMER" => performer = Some(unquote(rest).to_string()),
+            "FILE" => {
+                // the file type comes after the (possibly quoted) name
+                let name = if rest.starts_with('"') {
+                    unquote(rest)
+                } else {
+                    rest.rsplit_once(' ').map_or(rest, |(name, _)| name)
+                };
+                file = resolve(name, base, home);
+                in_track = false;
+            }
+            "TRACK" => {
+                in_track = file.is_some();
+                if let Some(path) = &file {
+                    let track = Entry {
+                        artist: performer.clone(),
+                        album: playlist.title.clone(),
+                        ..Entry::new(path.clone())
+                    };
+                    tracks.push((track, None));
+                } else {
+                    playlist.skipped += 1;
+                }
+            }
+            "INDEX" if in_track => {
+                let Some((number, time)) = rest.split_once(' ') else {
+                    continue;
+                };
+                let Some((track, pregap)) = tracks.last_mut() else {
+                    continue;
+                };
+
+                match number.parse::<u32>() {
+                    Ok(0) => *pregap = parse_cue_time(time),
+                    Ok(1) => track.start = parse_cue_time(time),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // each track ends where the next one in the same file begins
+    for i in 0..tracks.len() {
+        let (track, rest) = tracks[i..].split_first_mut().unwrap();
+        if let Some((next, pregap)) = rest.first() {
+            if next.path == track.0.path {
+                track.0.end = pregap.or(next.start);
+            }
+        }
+    }
+
+    playlist.entries = tracks.into_iter().map(|(track, _)| track).collect();
+    playlist
+}
+
+/// Parses the `<start>-<end>` of an `#EXTRANGE` tag, in seconds, where either
+/// can be left out.
+fn parse_range(range: &str) -> Option<(Option<f64>, Option<f64>)> {
+    let (start, end) = range.trim().split_once('-')?;
+    let bound = |secs: &str| match secs.trim() {
+        "" => Ok(None),
+        secs => secs.parse().map(Some),
+    };
+    Some((bound(start).ok()?, bound(end).ok()?))
+}
+
+/// Parses a `mm:ss:ff` timestamp (75 frames per second) into seconds.
+fn parse_cue_time(time: &str) -> Option<f64> {
+    let mut parts = time.trim().split(':').map(|p| p.parse::<u32>().ok());
+    let (mins, secs, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(mins as f64 * 60.0 + secs as f64 + frames as f64 / 75.0)
+}
+
+fn unquote(s: &str) -> &str {
+    match s.strip_prefix('"') {
+        Some(rest) => rest.split_once('"').map_or(rest, |(s, _)| s),
+        None => s,
+    }
+}
+
+/// Returns the contents of the first `<name>` element in `text`.
+fn element<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    next_element(text, name).map(|(contents, _)| contents)
+}
+
+/// Finds the next `<name>` element in `text`, returning its contents and the
+/// text after it.
+fn next_element<'a>(text: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+
+    let mut rest = text;
+    loop {
+        let idx = rest.find(&open)?;
+        rest = &rest[idx + open.len()..];
+
+        // make sure we didn't just find a prefix of a longer name
+        match rest.chars().next() {
+            Some('>') => break,
+            Some(ch) if ch.is_whitespace() => break,
+            _ => continue,
+        }
+    }
+
+    let start = rest.find('>')? + 1;
+    if rest[..start].ends_with("/>") {
+        return Some(("", &rest[start..]));
+    }
+
+    let rest = &rest[start..];
+    let end = rest.find(&close)?;
+    Some((rest[..end].trim(), &rest[end + close.len()..]))
+}
+
+/// Replaces the predefined XML entities and character references.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find('&') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+
+        let ch = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+
+        if let Some(ch) = ch {
+            out.push(ch);
+            rest = &rest[end + 1..];
+        } else {
+            out.push('&');
+            rest = &rest[1..];
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Resolves a path from a playlist in `base`.
+///
+/// `~/` is relative to the home directory, `file://` URIs are percent-decoded,
+/// and any other relative path is relative to the playlist. Returns `None` for
+/// any other URI scheme.
+pub fn resolve(entry: &str, base: &Path, home: &Path) -> Option<PathBuf> {
+    if let Some(uri) = entry.strip_prefix("file://") {
+        let uri = uri.strip_prefix("localhost").unwrap_or(uri);
+        Some(decode(uri))
+    } else if entry.contains("://") {
+        None
+    } else if let Some(rest) = entry.strip_prefix("~/") {
+        Some(home.join(rest))
+    } else {
+        Some(base.join(entry))
+    }
+}
+
+/// Like [`resolve`], but relative paths are percent-encoded URIs.
+fn resolve_uri(uri: &str, base: &Path, home: &Path) -> Option<PathBuf> {
+    if uri.contains("://") {
+        resolve(uri, base, home)
+    } else {
+        Some(base.join(decode(uri)))
+    }
+}
+
+fn decode(uri: &str) -> PathBuf {
+    OsString::from_vec(urlencoding::decode_binary(uri.as_bytes()).into_owned()).into()
+}
+
+/// Parses the contents of an `#EXTINF:<duration>,<artist> - <title>` line into
+/// the artist (if any) and title.
+///
+/// An empty artist (`#EXTINF:<duration>, - <title>`) lets the title contain
+/// ` - `.
+fn parse_extinf(ext: &str) -> Option<(Option<String>, String)> {
+    let (_duration, info) = ext.split_once(',')?;
+    let info = info.trim();
+    if info.is_empty() {
+        return None;
+    }
+
+    if let Some(title) = info.strip_prefix("- ") {
+        return Some((None, title.trim().to_string()));
+    }
+    Some(match info.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.trim().to_string()), title.trim().to_string()),
+        None => (None, info.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m3u(text: &str) -> Playlist {
+        parse(
+            Format::M3u,
+            text,
+            Path::new("/music"),
+            Path::new("/home/me"),
+        )
+    }
+
+    #[test]
+    fn parses_extended_m3u() {
+        let playlist = m3u("#EXTM3U\n\
+             #PLAYLIST:Mix\n\
+             #EXTALB:Album\n\
+             #EXTART:Band\n\
+             #EXTINF:123,Singer - Song\n\
+             one.mp3\n\
+             #EXTINF:-1,Other\n\
+             two.mp3\n\
+             three.mp3\n");
+
+        assert_eq!(playlist.title.as_deref(), Some("Mix"));
+        let entries = &playlist.entries;
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(entries[0].path, Path::new("/music/one.mp3"));
+        assert_eq!(entries[0].title.as_deref(), Some("Song"));
+        assert_eq!(entries[0].artist.as_deref(), Some("Singer"));
+        assert_eq!(entries[0].album.as_deref(), Some("Album"));
+
+        // the album artist stands in for a missing artist
+        assert_eq!(entries[1].title.as_deref(), Some("Other"));
+        assert_eq!(entries[1].artist.as_deref(), Some("Band"));
+
+        // `#EXTINF` only applies to the next entry
+        assert_eq!(entries[2].title, None);
+    }
+
+    #[test]
+    fn parses_custom_tags() {
+        let playlist = m3u("#EXTNOSHUFFLE\n\
+             #EXTNEXT:intro.mp3\n\
+             song.mp3\n\
+             other.mp3\n");
+
+        let entries = &playlist.entries;
+        assert!(entries[0].no_shuffle);
+        assert_eq!(
+            entries[0].next.as_deref(),
+            Some(Path::new("/music/intro.mp3"))
+        );
+        assert!(!entries[1].no_shuffle);
+        assert_eq!(entries[1].next, None);
+    }
+
+    #[test]
+    fn skips_remote_entries() {
+        let playlist = m3u("#EXTINF:-1,Radio\n\
+             http://example.com/stream\n\
+             #EXTNOSHUFFLE\n\
+             https://example.com/other\n\
+             song.mp3\n");
+
+        assert_eq!(playlist.skipped, 2);
+        assert_eq!(playlist.entries.len(), 1);
+        assert_eq!(playlist.entries[0].title, None);
+        assert!(!playlist.entries[0].no_shuffle);
+    }
+
+    #[test]
+    fn resolves_paths() {
+        let base = Path::new("/music/lists");
+        let home = Path::new("/home/me");
+        let resolve = |entry| resolve(entry, base, home);
+
+        assert_eq!(resolve("a.mp3"), Some("/music/lists/a.mp3".into()));
+        assert_eq!(resolve("../a.mp3"), Some("/music/lists/../a.mp3".into()));
+        assert_eq!(resolve("/abs/a.mp3"), Some("/abs/a.mp3".into()));
+        assert_eq!(resolve("~/a.mp3"), Some("/home/me/a.mp3".into()));
+        assert_eq!(resolve("file:///a%20b.mp3"), Some("/a b.mp3".into()));
+        assert_eq!(resolve("file://localhost/a.mp3"), Some("/a.mp3".into()));
+        assert_eq!(resolve("http://example.com/a.mp3"), None);
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(Format::from_path("a.M3U"), Some(Format::M3u));
+        assert_eq!(Format::from_path("a.m3u4"), Some(Format::M3u));
+        assert_eq!(Format::from_path("a.m3u8"), Some(Format::M3u8));
+        assert_eq!(Format::from_path("a.pls"), Some(Format::Pls));
+        assert_eq!(Format::from_path("a.xspf"), Some(Format::Xspf));
+        assert_eq!(Format::from_path("a.cue"), Some(Format::Cue));
+        assert_eq!(Format::from_path("a.mp3"), None);
+    }
+
+    #[test]
+    fn parses_pls() {
+        let text = "[playlist]\n\
+             X-Title=Mix\n\
+             File2=two.mp3\n\
+             Title2=Second\n\
+             File1=one.mp3\n\
+             Title1=\n\
+             File3=http://example.com/stream\n\
+             NumberOfEntries=3\n";
+        let playlist = parse(Format::Pls, text, Path::new("/music"), Path::new("/"));
+
+        assert_eq!(playlist.title.as_deref(), Some("Mix"));
+        assert_eq!(playlist.skipped, 1);
+        let entries = &playlist.entries;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, Path::new("/music/one.mp3"));
+        assert_eq!(entries[0].title, None);
+        assert_eq!(entries[1].path, Path::new("/music/two.mp3"));
+        assert_eq!(entries[1].title.as_deref(), Some("Second"));
+    }
+
+    #[test]
+    fn parses_xspf() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <title>Mix &amp; Match</title>
+              <trackList>
+                <track>
+                  <location>file:///music/a%20b.mp3</location>
+                  <title>Song &#233;</title>
+                  <creator>Singer</creator>
+                  <album>Album</album>
+                </track>
+                <track>
+                  <location>rel/c.mp3</location>
+                  <titles/>
+                </track>
+                <track>
+                  <location>http://example.com/stream</location>
+                </track>
+              </trackList>
+            </playlist>"#;
+        let playlist = parse(Format::Xspf, text, Path::new("/lists"), Path::new("/"));
+
+        assert_eq!(playlist.title.as_deref(), Some("Mix & Match"));
+        assert_eq!(playlist.skipped, 1);
+        let entries = &playlist.entries;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, Path::new("/music/a b.mp3"));
+        assert_eq!(entries[0].title.as_deref(), Some("Song é"));
+        assert_eq!(entries[0].artist.as_deref(), Some("Singer"));
+        assert_eq!(entries[0].album.as_deref(), Some("Album"));
+        assert_eq!(entries[1].path, Path::new("/lists/rel/c.mp3"));
+        assert_eq!(entries[1].title, None);
+    }
+
+    #[test]
+    fn parses_cue() {
+        let text = r#"PERFORMER "Band"
+            TITLE "Album"
+            FILE "album.flac" WAVE
+              TRACK 01 AUDIO
+                TITLE "One"
+                INDEX 01 00:00:00
+              TRACK 02 AUDIO
+                TITLE "Two"
+                PERFORMER "Guest"
+                INDEX 00 01:00:00
+                INDEX 01 01:02:37
+              TRACK 03 AUDIO
+                TITLE "Three"
+                INDEX 01 02:00:00
+            FILE bonus.flac WAVE
+              TRACK 04 AUDIO
+                INDEX 01 00:00:00
+            "#;
+        let playlist = parse(Format::Cue, text, Path::new("/music"), Path::new("/"));
+
+        assert_eq!(playlist.title.as_deref(), Some("Album"));
+        let entries = &playlist.entries;
+        assert_eq!(entries.len(), 4);
+
+        assert_eq!(entries[0].path, Path::new("/music/album.flac"));
+        assert_eq!(entries[0].title.as_deref(), Some("One"));
+        assert_eq!(entries[0].artist.as_deref(), Some("Band"));
+        assert_eq!(entries[0].album.as_deref(), Some("Album"));
+        // tracks end where the next one's pregap starts
+        assert_eq!((entries[0].start, entries[0].end), (Some(0.0), Some(60.0)));
+
+        assert_eq!(entries[1].artist.as_deref(), Some("Guest"));
+        assert_eq!(
+            (entries[1].start, entries[1].end),
+            (Some(62.0 + 37.0 / 75.0), Some(120.0))
+        );
+
+        // the last track in a file runs to its end
+        assert_eq!((entries[2].start, entries[2].end), (Some(120.0), None));
+
+        assert_eq!(entries[3].path, Path::new("/music/bonus.flac"));
+        assert_eq!((entries[3].start, entries[3].end), (Some(0.0), None));
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let playlist = m3u("#EXTRANGE:1.5-60\n\
+             one.flac\n\
+             #EXTRANGE:60-\n\
+             two.flac\n\
+             #EXTRANGE:nonsense\n\
+             three.flac\n");
+
+        let entries = &playlist.entries;
+        assert_eq!((entries[0].start, entries[0].end), (Some(1.5), Some(60.0)));
+        assert_eq!((entries[1].start, entries[1].end), (Some(60.0), None));
+        assert_eq!((entries[2].start, entries[2].end), (None, None));
+    }
+
+    /// Writes `entries` to an `m3u` file and parses it back.
+    fn round_trip(title: Option<&str>, entries: Vec<Entry>) -> Playlist {
+        let path = std::env::temp_dir().join(format!(
+            "cramp-playlist-{}-{}.m3u",
+            std::process::id(),
+            entries.len()
+        ));
+        let home = Path::new("/home/me");
+        write_m3u(&path, title, entries, Some(home)).unwrap();
+        let text = read(&path, Format::M3u).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        parse(Format::M3u, &text, Path::new("/elsewhere"), home)
+    }
+
+    #[test]
+    fn round_trips_m3u() {
+        let playlist = m3u("#EXTM3U\n\
+             #PLAYLIST:Mix\n\
+             #EXTINF:-1,Singer - Song\n\
+             #EXTNOSHUFFLE\n\
+             one.mp3\n\
+             #EXTNEXT:~/intro.mp3\n\
+             two.mp3\n\
+             /home/me/intro.mp3\n");
+        let again = round_trip(playlist.title.as_deref(), playlist.entries.clone());
+
+        assert_eq!(again.title.as_deref(), Some("Mix"));
+        assert_eq!(again.entries.len(), 3);
+        for (entry, again) in playlist.entries.iter().zip(&again.entries) {
+            assert_eq!(entry.path, again.path);
+            assert_eq!(entry.title, again.title);
+            assert_eq!(entry.artist, again.artist);
+            assert_eq!(entry.next, again.next);
+            assert_eq!(entry.no_shuffle, again.no_shuffle);
+        }
+    }
+
+    #[test]
+    fn round_trips_titles_with_dashes() {
+        let entry = |title: &str, artist: Option<&str>| Entry {
+            title: Some(title.into()),
+            artist: artist.map(Into::into),
+            ..Entry::new("/music/song.mp3".into())
+        };
+        let entries = vec![
+            entry("Live - 1999", None),
+            entry("- Intro", None),
+            entry("Live - 1999", Some("Band")),
+        ];
+        let again = round_trip(None, entries.clone());
+
+        for (entry, again) in entries.iter().zip(&again.entries) {
+            assert_eq!(entry.title, again.title);
+            assert_eq!(entry.artist, again.artist);
+        }
+    }
+
+    #[test]
+    fn round_trips_cue_tracks() {
+        let text = r#"PERFORMER "Band"
+            FILE "album.flac" WAVE
+              TRACK 01 AUDIO
+                TITLE "One"
+                INDEX 01 00:00:00
+              TRACK 02 AUDIO
+                TITLE "Two"
+                INDEX 01 01:02:37
+            "#;
+        let playlist = parse(Format::Cue, text, Path::new("/music"), Path::new("/"));
+        let again = round_trip(None, playlist.entries.clone());
+
+        assert_eq!(again.entries.len(), 2);
+        for (entry, again) in playlist.entries.iter().zip(&again.entries) {
+            assert_eq!(entry.path, again.path);
+            assert_eq!(entry.title, again.title);
+            assert_eq!(entry.artist, again.artist);
+            assert_eq!((entry.start, entry.end), (again.start, again.end));
+        }
+    }
+
+    #[test]
+    fn parses_extinf() {
+        assert_eq!(
+            parse_extinf("10,A - B - C"),
+            Some((Some("A".into()), "B - C".into()))
+        );
+        assert_eq!(parse_extinf("-1,Title"), Some((None, "Title".into())));
+        assert_eq!(
+            parse_extinf("-1, - Live - 1999"),
+            Some((None, "Live - 1999".into()))
+        );
+        assert_eq!(parse_extinf("-1,"), None);
+        assert_eq!(parse_extinf("-1"), None);
+    }
+}
@@ -1,14 +1,20 @@
 use circular_buffer::CircularBuffer;
 use rand::{seq::SliceRandom, thread_rng};
 
-use std::collections::VecDeque;
-use std::path::Path;
-use std::{fmt, fs};
-
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::num::ParseIntError;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::playlist::Playlist;
+use crate::scanner::{self, Job, Scanned, Shared};
 use crate::song::Song;
-use crate::Message;
+use crate::{Message, Source};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Key(usize);
 
 impl fmt::Display for Key {
@@ -17,9 +23,38 @@ impl fmt::Display for Key {
     }
 }
 
+impl FromStr for Key {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
 #[derive(Default)]
 pub struct Queue {
-    songs: Vec<Song>,
+    config: Arc<Config>,
+
+    /// Every song loaded, indexed by `Key`.
+    ///
+    /// Never reordered, so keys stay valid; see `order` for sorting. Removed
+    /// songs leave an empty slot behind, so their keys aren't reused.
+    songs: Vec<Option<Song>>,
+
+    /// The order to list `songs` in.
+    order: Vec<Key>,
+
+    /// Whether `order` is sorted by name.
+    sorted: bool,
+
+    /// The first song loaded from each path.
+    by_path: HashMap<PathBuf, Key>,
+
+    /// The folders scanned.
+    dirs: HashSet<PathBuf>,
+
+    /// The playlists loaded.
+    playlists: HashSet<PathBuf>,
 
     /// The list of all songs to play.
     playlist: VecDeque<Key>,
@@ -42,46 +77,124 @@ pub struct Queue {
     ///
     /// Keeps up to 32 entries.
     history: CircularBuffer<32, Key>,
+
+    /// Incremented whenever `songs` (or `dirs`) changes, so views can
+    /// invalidate caches.
+    generation: usize,
 }
 
 impl Queue {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
     }
 
-    // FIXME: is invalidating each key an issue? tbd
+    /// Sorts the song list by name, keeping it sorted as songs are added.
     pub fn sort_songs(&mut self) {
-        self.songs.sort_by_key(|song| song.name.clone());
+        self.sorted = true;
+        self.songs_added(0);
+    }
+
+    /// Sorts the songs pushed onto `order` from `from` on into the rest, if
+    /// it's sorted, and lets views know the songs changed.
+    ///
+    /// Done once per batch of songs: the batch is sorted on its own, then
+    /// merged into the rest in one pass, rather than each song being inserted
+    /// (and the songs after it moved) one by one.
+    fn songs_added(&mut self, from: usize) {
+        if self.sorted && from < self.order.len() {
+            let mut old = std::mem::take(&mut self.order);
+            let mut new = old.split_off(from);
+            let name = |id: &Key| self.get(*id).map(|song| song.name.as_str());
+            new.sort_by_key(name);
+
+            // songs already listed go before new ones with the same name
+            let mut order = Vec::with_capacity(old.len() + new.len());
+            let mut new = new.into_iter().peekable();
+            for id in old {
+                while let Some(next) = new.next_if(|next| name(next) < name(&id)) {
+                    order.push(next);
+                }
+                order.push(id);
+            }
+            order.extend(new);
+            self.order = order;
+        }
+        self.generation += 1;
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Returns the song with the key `id`, if it hasn't been removed.
+    pub fn get(&self, id: Key) -> Option<&Song> {
+        self.songs.get(id.0).and_then(Option::as_ref)
+    }
+
+    /// Marks a song as unplayable (or playable again, given `None`).
+    pub fn set_broken(&mut self, id: Key, reason: Option<String>) {
+        if let Some(Some(song)) = self.songs.get_mut(id.0) {
+            song.broken = reason;
+        }
+    }
+
+    /// Every song that couldn't be played, in song list order.
+    pub fn broken(&self) -> impl Iterator<Item = (Key, &Song)> {
+        self.order.iter().filter_map(|id| {
+            let song = self.get(*id)?;
+            song.broken.is_some().then_some((*id, song))
+        })
     }
 
-    pub fn get(&self, id: Key) -> &Song {
-        self.songs.get(id.0).expect("given invalid key")
+    /// Whether `id` refers to a song that hasn't been removed.
+    pub fn contains(&self, id: Key) -> bool {
+        self.songs.get(id.0).is_some_and(Option::is_some)
     }
 
     pub fn songs(&self) -> impl Iterator<Item = (Key, &Song)> {
-        self.songs
-            .iter()
-            .enumerate()
-            .filter_map(|(key, song)| song.user_added.then_some((Key(key), song)))
+        self.order.iter().filter_map(|id| {
+            let song = self.get(*id)?;
+            song.user_added.then_some((*id, song))
+        })
     }
 
     pub fn current(&self) -> Option<&Song> {
-        self.current.map(|id| self.get(id))
+        self.current.and_then(|id| self.get(id))
     }
 
     pub fn current_id(&self) -> Option<Key> {
         self.current
     }
 
+    pub fn next_id(&self) -> Option<Key> {
+        self.playlist.front().copied()
+    }
+
     pub fn next(&self) -> Option<&Song> {
-        self.playlist.front().map(|id| self.get(*id))
+        self.playlist.front().and_then(|id| self.get(*id))
     }
 
     pub fn playlist(&self) -> impl Iterator<Item = &Song> + '_ {
-        self.playlist.iter().map(|id| self.get(*id)).skip(1)
+        self.upcoming().skip(1)
+    }
+
+    /// Every song left to play, including the next one.
+    pub fn upcoming(&self) -> impl Iterator<Item = &Song> + '_ {
+        self.playlist.iter().filter_map(|id| self.get(*id))
     }
 
-    pub fn advance(&mut self) {
+    /// The songs played to completion, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Song> + '_ {
+        self.history.iter().filter_map(|id| self.get(*id))
+    }
+
+    /// Moves on to the next song, returning why its `#EXTNEXT` song was
+    /// skipped, if it was.
+    pub fn advance(&mut self) -> Option<Message> {
+        let mut message = None;
         if let Some(id) = self.current.take() {
             self.history.push_back(id);
         }
@@ -94,11 +207,22 @@ impl Queue {
         if let Some(id) = self.playlist.pop_front() {
             self.current = Some(id);
 
-            if let Some(path) = &self.get(id).next {
-                // FIXME: deduplicate please
-                let mut song = Song::new(path);
-                song.user_added = false;
-                let id = self.add_song(song);
+            let next = self.get(id).and_then(|song| song.next.clone());
+            let next = next.and_then(|path| match self.find(&path) {
+                Some(id) => Some(id),
+                None => match Song::new(path) {
+                    Ok(mut song) => {
+                        song.user_added = false;
+                        Some(self.add_song(song))
+                    }
+                    Err(e) => {
+                        message = Some(e.into());
+                        None
+                    }
+                },
+            });
+
+            if let Some(id) = next {
                 self.playlist.push_front(id);
                 self.explicit_next = true;
             } else {
@@ -111,6 +235,8 @@ impl Queue {
             // TODO: make this an option
             self.queue_all();
         }
+
+        message
     }
 
     pub fn previous(&mut self) {
@@ -123,6 +249,13 @@ impl Queue {
         }
     }
 
+    /// Stops playing the current song, leaving it to play next.
+    pub fn stop(&mut self) {
+        if let Some(id) = self.current.take() {
+            self.playlist.push_front(id);
+        }
+    }
+
     pub fn shuffle(&mut self) {
         self.playlist.make_contiguous();
         self.playlist.as_mut_slices().0[self.user_queue..].shuffle(&mut thread_rng());
@@ -133,7 +266,11 @@ impl Queue {
         self.shuffle
     }
 
-    pub fn play(&mut self, id: Key) -> &Song {
+    pub fn play(&mut self, id: Key) -> Option<&Song> {
+        if !self.contains(id) {
+            return None;
+        }
+
         if let Some(id) = self.current.take() {
             self.history.push_back(id);
         }
@@ -142,7 +279,27 @@ impl Queue {
         self.get(id)
     }
 
-    pub fn set_next(&mut self, id: Key) -> &Song {
+    /// Plays the first song now, and the rest right after it.
+    pub fn play_all(&mut self, ids: &[Key]) -> Option<&Song> {
+        let (first, rest) = ids.split_first()?;
+
+        for (i, id) in rest.iter().enumerate() {
+            self.playlist.insert(i, *id);
+        }
+
+        if !rest.is_empty() {
+            self.explicit_next = false;
+            self.user_queue += rest.len();
+        }
+
+        self.play(*first)
+    }
+
+    pub fn set_next(&mut self, id: Key) -> Option<&Song> {
+        if !self.contains(id) {
+            return None;
+        }
+
         if self.explicit_next {
             self.playlist[0] = id;
         } else {
@@ -154,12 +311,184 @@ impl Queue {
         self.get(id)
     }
 
+    /// Plays the given songs next, in order.
+    pub fn set_next_all(&mut self, ids: &[Key]) -> Option<&Song> {
+        let (first, rest) = ids.split_first()?;
+        self.set_next(*first)?;
+
+        for (i, id) in rest.iter().enumerate() {
+            self.playlist.insert(i + 1, *id);
+        }
+        self.user_queue += rest.len();
+
+        self.get(*first)
+    }
+
     pub fn add_song(&mut self, song: Song) -> Key {
-        self.songs.push(song);
-        Key(self.songs.len() - 1)
+        let from = self.order.len();
+        let id = self.push_song(song);
+        self.songs_added(from);
+        id
+    }
+
+    /// Adds a song without sorting it in; see [`Self::songs_added`].
+    fn push_song(&mut self, song: Song) -> Key {
+        let id = Key(self.songs.len());
+        // songs loaded by themselves win over ones from playlists
+        let first = self.by_path.entry(song.path.clone()).or_insert(id);
+        let other = self.songs.get(first.0).and_then(Option::as_ref);
+        if song.source.is_none() && other.is_some_and(|other| other.source.is_some()) {
+            *first = id;
+        }
+        self.order.push(id);
+        self.songs.push(Some(song));
+        id
+    }
+
+    /// Removes a song, forgetting it wherever it's queued.
+    pub fn remove(&mut self, id: Key) -> Option<Song> {
+        let song = self.songs.get_mut(id.0)?.take()?;
+        self.forget(&HashSet::from([id]));
+        Some(song)
+    }
+
+    /// Removes the songs with the keys `ids`, returning those that hadn't been
+    /// removed already.
+    pub fn remove_many(&mut self, ids: &[Key]) -> Vec<Key> {
+        let mut removed = HashSet::new();
+        for id in ids {
+            if self.songs.get_mut(id.0).and_then(Option::take).is_some() {
+                removed.insert(*id);
+            }
+        }
+
+        self.forget(&removed);
+        removed.into_iter().collect()
+    }
+
+    /// Removes every song matching `remove`, returning their keys.
+    pub fn remove_where(&mut self, mut remove: impl FnMut(&Song) -> bool) -> Vec<Key> {
+        let mut removed = HashSet::new();
+        for (i, slot) in self.songs.iter_mut().enumerate() {
+            if slot.as_ref().is_some_and(&mut remove) {
+                *slot = None;
+                removed.insert(Key(i));
+            }
+        }
+
+        self.forget(&removed);
+        removed.into_iter().collect()
+    }
+
+    /// Removes every song inside the directory `prefix` (or the song at it).
+    pub fn remove_path(&mut self, prefix: impl AsRef<Path>) -> Vec<Key> {
+        let prefix = scanner::absolute(prefix);
+        self.dirs.retain(|dir| !dir.starts_with(&prefix));
+        self.remove_where(|song| song.path.starts_with(&prefix))
+    }
+
+    /// Removes every song loaded from the playlist at `path`.
+    pub fn remove_source(&mut self, path: impl AsRef<Path>) -> Vec<Key> {
+        let path = scanner::absolute(path);
+        self.playlists.remove(&path);
+        self.remove_where(|song| song.source.as_deref() == Some(&path))
+    }
+
+    /// Loads the playlist at `path` again, replacing the songs loaded from it.
+    ///
+    /// The current song keeps playing, but no longer counts as loaded from
+    /// the playlist.
+    pub fn reload_playlist(&mut self, path: impl AsRef<Path>) -> Vec<Message> {
+        let path = scanner::absolute(path);
+        if let Some(Some(song)) = self.current.map(|id| &mut self.songs[id.0]) {
+            if song.source.as_deref() == Some(&path) {
+                song.source = None;
+            }
+        }
+
+        self.remove_where(|song| song.source.as_deref() == Some(&path));
+        self.load_playlist(path)
+    }
+
+    /// Drops any references to removed songs.
+    fn forget(&mut self, removed: &HashSet<Key>) {
+        if removed.is_empty() {
+            return;
+        }
+
+        self.order.retain(|id| !removed.contains(id));
+
+        if self.playlist.front().is_some_and(|id| removed.contains(id)) {
+            self.explicit_next = false;
+        }
+        let user_queued = self
+            .playlist
+            .iter()
+            .take(self.user_queue)
+            .filter(|id| removed.contains(id))
+            .count();
+        self.user_queue -= user_queued.min(self.user_queue);
+        self.playlist.retain(|id| !removed.contains(id));
+
+        if self.current.is_some_and(|id| removed.contains(&id)) {
+            self.current = None;
+        }
+
+        let history: Vec<_> = self
+            .history
+            .iter()
+            .filter(|id| !removed.contains(id))
+            .copied()
+            .collect();
+        self.history.clear();
+        self.history.extend_from_slice(&history);
+
+        // other songs may share a path with a removed one (e.g. from cue
+        // sheets), again preferring ones loaded by themselves
+        self.by_path.retain(|_, id| !removed.contains(id));
+        let mut others: Vec<_> = self
+            .songs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, song)| Some((Key(i), song.as_ref()?)))
+            .collect();
+        others.sort_by_key(|(_, song)| song.source.is_some());
+        for (id, song) in others {
+            self.by_path.entry(song.path.clone()).or_insert(id);
+        }
+
+        self.generation += 1;
+    }
+
+    /// Whether the folder `dir` was scanned.
+    pub fn has_dir(&self, dir: impl AsRef<Path>) -> bool {
+        self.dirs.contains(dir.as_ref())
+    }
+
+    /// Whether the playlist at `path` was loaded.
+    pub fn has_playlist(&self, path: impl AsRef<Path>) -> bool {
+        self.playlists.contains(path.as_ref())
+    }
+
+    /// The directories songs were loaded from, including the folders
+    /// playlists are in.
+    pub fn dirs(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.dirs
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(self.playlists.iter().filter_map(|path| path.parent()))
+    }
+
+    /// Finds a song loaded from `path`.
+    pub fn find(&self, path: impl AsRef<Path>) -> Option<Key> {
+        self.by_path.get(path.as_ref()).copied()
     }
 
     pub fn queue(&mut self, id: Key, user_queue: bool) {
+        if !self.contains(id) {
+            return;
+        }
+
         if user_queue {
             self.playlist.insert(self.user_queue, id);
             self.user_queue += 1;
@@ -168,128 +497,344 @@ impl Queue {
         }
     }
 
+    /// Queues the given songs, in order.
+    pub fn queue_many(&mut self, ids: &[Key], user_queue: bool) {
+        for id in ids {
+            self.queue(*id, user_queue);
+        }
+    }
+
     pub fn queue_all(&mut self) {
         self.playlist = self
             .songs()
-            .filter_map(|(id, song)| (song.user_added && !song.no_shuffle).then_some(id))
+            .filter_map(|(id, song)| {
+                (song.user_added && !song.no_shuffle && song.broken.is_none()).then_some(id)
+            })
             .collect();
 
         self.explicit_next = false;
         self.user_queue = 0;
     }
 
+    /// Loads a song, folder or playlist, all at once.
     pub fn load(&mut self, path: impl AsRef<Path>) -> impl Iterator<Item = Message> {
-        let path = path.as_ref();
-        if path.is_dir() {
-            self.load_dir(path)
-        } else if path.is_file() {
-            let Some(ext) = path.extension().map(|s| s.to_string_lossy()) else {
-                return Vec::new().into_iter();
-            };
+        let mut messages = Vec::new();
+        let mut skipped = 0;
+
+        // not cached, as anything loaded this way (from the watcher, say) has
+        // changed since the last scan anyway
+        let shared = Shared::default();
+        let mut jobs = vec![Job::new(path.as_ref())];
+        while let Some(job) = jobs.pop() {
+            let (scanned, dirs) = scanner::scan(&job, &self.config, &shared);
+            skipped += scanned.skipped;
+            messages.extend(self.add_scanned(scanned));
+            jobs.extend(dirs.into_iter().rev());
+        }
 
-            match ext.as_ref() {
-                "m3u" | "m3u4" => self.load_playlist(path),
-                _ => {
-                    let song = Song::new(path);
-                    let id = self.add_song(song);
-                    self.playlist.push_back(id);
-                    Vec::new()
-                }
-            }
-        } else {
-            Vec::new()
+        if skipped > 0 {
+            messages.push(
+                Message::new(format!("skipped {skipped} non-audio files")).source(Source::Library),
+            );
         }
-        .into_iter()
+        messages.into_iter()
     }
 
-    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Vec<Message> {
-        let read = match fs::read_dir(&dir) {
-            Ok(r) => r,
-            Err(e) => {
-                return vec![Message::new(format!(
-                    "failed to enumerate {}: {e}",
-                    dir.as_ref().display()
-                ))];
+    /// Adds what was found by a scan, queueing the songs. Folders, songs and
+    /// playlists that are already loaded are skipped, so opening them again
+    /// doesn't list them twice.
+    pub fn add_scanned(&mut self, scanned: Scanned) -> Vec<Message> {
+        let mut messages = scanned.messages;
+
+        let (new_dir, songs) = match scanned.dir {
+            // a new folder is worth watching even if it's empty for now
+            Some(dir) if !self.dirs.contains(&dir) => {
+                self.dirs.insert(dir);
+                (true, scanned.songs)
+            }
+            Some(_) => return messages,
+            None => {
+                let songs = scanned.songs.into_iter();
+                (
+                    false,
+                    songs.filter(|song| !self.has_song(&song.path)).collect(),
+                )
             }
         };
+        if new_dir || !songs.is_empty() {
+            let from = self.order.len();
+            for song in songs {
+                let id = self.push_song(song);
+                self.playlist.push_back(id);
+            }
+            self.songs_added(from);
+        }
 
-        let mut messages = Vec::new();
-        for entry in read {
-            match entry {
-                Ok(entry) => {
-                    messages.extend(self.load(entry.path()));
-                }
-                Err(e) => {
-                    messages.push(Message::new(format!(
-                        "failed to read {}: {e}",
-                        dir.as_ref().display()
-                    )));
-                }
+        for (path, playlist) in scanned.playlists {
+            if !self.playlists.contains(&path) {
+                messages.extend(self.add_playlist(&path, playlist));
             }
         }
 
         messages
     }
 
+    /// Whether the song at `path` was loaded by itself or from its folder,
+    /// rather than only from playlists.
+    fn has_song(&self, path: &Path) -> bool {
+        let found = self.find(path).and_then(|id| self.get(id));
+        found.is_some_and(|song| song.source.is_none())
+    }
+
+    /// Whether a file or folder found in a folder should be loaded.
+    pub fn loadable(&self, path: impl AsRef<Path>) -> bool {
+        scanner::loadable(path.as_ref(), &self.config)
+    }
+
     pub fn load_playlist(&mut self, path: impl AsRef<Path>) -> Vec<Message> {
+        let path = scanner::absolute(path);
+        match scanner::read_playlist(&path) {
+            Ok(mut playlist) => {
+                scanner::read_tags(&mut playlist, None);
+                self.add_playlist(&path, playlist)
+            }
+            Err(e) => vec![e],
+        }
+    }
+
+    fn add_playlist(&mut self, path: &Path, playlist: Playlist) -> Vec<Message> {
         let mut messages = Vec::new();
+        self.playlists.insert(path.to_path_buf());
+
+        let from = self.order.len();
+        let mut loaded = 0;
+        for entry in playlist.entries {
+            let no_shuffle = entry.no_shuffle;
+            let song = match entry.into_song() {
+                Ok(song) => song,
+                Err(e) => {
+                    messages.push(e.into());
+                    continue;
+                }
+            };
+            let id = self.push_song(song.source(Some(path)));
+            loaded += 1;
 
-        let playlist = match std::fs::read_to_string(path) {
-            Ok(p) => p,
-            Err(e) => {
-                messages.push(Message::new(format!("failed to load playlist: {e}")));
-                return messages;
+            if !no_shuffle {
+                self.playlist.push_back(id);
             }
+        }
+        if loaded > 0 {
+            self.songs_added(from);
+        }
+
+        let loaded = if let Some(title) = playlist.title {
+            format!("loaded {loaded} songs from playlist {title}")
+        } else {
+            format!("loaded {loaded} songs from playlist")
         };
+        messages.push(Message::new(loaded).source(Source::Playlist));
+
+        if playlist.skipped > 0 {
+            let skipped = format!("skipped {} non-local songs in playlist", playlist.skipped);
+            messages.push(Message::new(skipped).source(Source::Playlist));
+        }
 
-        let home = dirs::home_dir().unwrap_or_else(|| {
-            messages.push(Message::stc(
-                "failed to get home directory! defaulting to cwd",
-            ));
-            "./".into()
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(path: &str) -> Song {
+        Song::new(path).unwrap()
+    }
+
+    fn names(queue: &Queue) -> Vec<&str> {
+        queue.songs().map(|(_, song)| song.name.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_songs_sorted() {
+        let mut queue = Queue::default();
+        queue.add_song(song("/b.mp3"));
+        queue.add_song(song("/a.mp3"));
+        assert_eq!(names(&queue), ["b", "a"]);
+
+        queue.sort_songs();
+        assert_eq!(names(&queue), ["a", "b"]);
+
+        queue.add_song(song("/c.mp3"));
+        queue.add_song(song("/ab.mp3"));
+        assert_eq!(names(&queue), ["a", "ab", "b", "c"]);
+    }
+
+    #[test]
+    fn sorts_scanned_songs_once() {
+        let mut queue = Queue::default();
+        queue.sort_songs();
+        queue.add_song(song("/m.mp3"));
+
+        let generation = queue.generation();
+        queue.add_scanned(Scanned {
+            songs: ["/z.mp3", "/a.mp3", "/n.mp3"].map(song).into(),
+            ..Scanned::default()
         });
+        assert_eq!(names(&queue), ["a", "m", "n", "z"]);
+        assert_eq!(queue.generation(), generation + 1);
 
-        let mut next = None;
-        let mut no_shuffle = false;
-        for line in playlist.lines() {
-            if let Some(ext) = line.strip_prefix('#') {
-                if ext.trim() == "EXTNOSHUFFLE" {
-                    no_shuffle = true;
-                } else if let Some(path) = ext.strip_prefix("EXTNEXT:") {
-                    let path = if let Some(rest) = path.strip_prefix("~/") {
-                        let mut path = home.clone();
-                        path.push(rest);
-                        path
-                    } else {
-                        path.into()
-                    };
-
-                    next = Some(path);
-                }
-            } else {
-                let path = if let Some(rest) = line.strip_prefix("~/") {
-                    let mut path = home.clone();
-                    path.push(rest);
-                    path
-                } else {
-                    line.into()
-                };
-
-                let id = self.add_song(Song::new(path).next(next.take()).no_shuffle(no_shuffle));
-
-                if !no_shuffle {
-                    self.playlist.push_back(id);
-                }
+        // scanned songs are queued in the order they were found
+        let upcoming: Vec<_> = queue.upcoming().map(|song| song.name.as_str()).collect();
+        assert_eq!(upcoming, ["z", "a", "n"]);
+    }
 
-                no_shuffle = false;
-            }
-        }
+    #[test]
+    fn loads_folders_once() {
+        let dir = std::env::temp_dir().join(format!("cramp-queue-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.mp3"), b"").unwrap();
+        std::fs::write(dir.join("sub/b.mp3"), b"").unwrap();
+        std::fs::write(dir.join("list.m3u"), "a.mp3\nsub/b.mp3\n").unwrap();
+
+        let mut queue = Queue::default();
+        queue.load(&dir).for_each(drop);
+        assert_eq!(queue.songs().count(), 4);
+
+        queue.load(&dir).for_each(drop);
+        queue.load(dir.join("sub")).for_each(drop);
+        queue.load(dir.join("a.mp3")).for_each(drop);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(queue.songs().count(), 4);
+    }
 
-        messages.push(Message::new(format!(
-            "loaded {} songs from playlist",
-            self.songs.len()
-        )));
+    #[test]
+    fn lists_new_songs_after_old_ones_with_the_same_name() {
+        let mut queue = Queue::default();
+        queue.sort_songs();
+        let old = queue.add_song(song("/old/a.mp3"));
+        queue.add_scanned(Scanned {
+            songs: ["/new/b.mp3", "/new/a.mp3", "/new/0.mp3"].map(song).into(),
+            ..Scanned::default()
+        });
 
-        messages
+        let paths: Vec<_> = queue.songs().map(|(_, song)| song.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            ["/new/0.mp3", "/old/a.mp3", "/new/a.mp3", "/new/b.mp3"].map(Path::new)
+        );
+        assert_eq!(queue.songs().nth(1).map(|(id, _)| id), Some(old));
+    }
+
+    #[test]
+    fn goes_back_and_stops() {
+        let mut queue = Queue::default();
+        let ids: Vec<_> = ["/a.mp3", "/b.mp3", "/c.mp3"]
+            .map(|path| queue.add_song(song(path)))
+            .into();
+        queue.queue_many(&ids, false);
+        queue.advance();
+        queue.advance();
+        assert_eq!(queue.current_id(), Some(ids[1]));
+
+        queue.previous();
+        assert_eq!(queue.current_id(), Some(ids[0]));
+        assert_eq!(queue.next_id(), Some(ids[1]));
+
+        // with no history left, there's nothing to go back to
+        queue.previous();
+        assert_eq!(queue.current_id(), None);
+        assert_eq!(queue.next_id(), Some(ids[0]));
+
+        queue.advance();
+        queue.stop();
+        assert_eq!(queue.current_id(), None);
+        assert_eq!(queue.next_id(), Some(ids[0]));
+    }
+
+    #[test]
+    fn reuses_extnext_songs() {
+        let mut queue = Queue::default();
+        let a = queue.add_song(song("/a.mp3").next(Some("/intro.mp3")));
+        queue.queue(a, false);
+
+        queue.advance();
+        let intro = queue.next_id().unwrap();
+        assert_eq!(queue.get(intro).unwrap().path, Path::new("/intro.mp3"));
+        // songs only played as an `#EXTNEXT` aren't listed
+        assert_eq!(names(&queue), ["a"]);
+
+        queue.advance();
+        queue.queue(a, false);
+        queue.advance();
+        assert_eq!(queue.current_id(), Some(a));
+        assert_eq!(queue.next_id(), Some(intro));
+        assert!(queue.get(Key(2)).is_none());
+    }
+
+    #[test]
+    fn forgets_removed_songs() {
+        let mut queue = Queue::default();
+        let ids: Vec<_> = ["/a.mp3", "/b.mp3", "/c.mp3", "/d.mp3"]
+            .map(|path| queue.add_song(song(path)))
+            .into();
+        queue.queue_many(&ids, false);
+        queue.advance();
+        queue.set_next(ids[3]);
+        assert_eq!(queue.current_id(), Some(ids[0]));
+
+        let mut removed = queue.remove_many(&[ids[0], ids[3], ids[3]]);
+        removed.sort();
+        assert_eq!(removed, [ids[0], ids[3]]);
+        assert_eq!(queue.remove_many(&[ids[0]]), []);
+
+        assert!(!queue.contains(ids[0]));
+        assert_eq!(queue.current_id(), None);
+        assert_eq!(queue.next_id(), Some(ids[1]));
+        assert_eq!(names(&queue), ["b", "c"]);
+        assert_eq!(queue.find("/d.mp3"), None);
+    }
+
+    #[test]
+    fn finds_other_songs_at_a_removed_path() {
+        let mut queue = Queue::default();
+        let first = queue.add_song(song("/album.flac").bounds(Some(0.0), Some(60.0)));
+        let second = queue.add_song(song("/album.flac").bounds(Some(60.0), None));
+        assert_eq!(queue.find("/album.flac"), Some(first));
+
+        queue.remove(first);
+        assert_eq!(queue.find("/album.flac"), Some(second));
+    }
+
+    #[test]
+    fn reloads_playlists_by_relative_path() {
+        let dir = std::env::current_dir().unwrap();
+        let name = format!("cramp-reload-{}.m3u", std::process::id());
+        std::fs::write(dir.join(&name), "/music/a.mp3\n/music/b.mp3\n").unwrap();
+
+        let mut queue = Queue::default();
+        queue.load_playlist(dir.join(&name));
+        queue.reload_playlist(&name);
+        std::fs::remove_file(dir.join(&name)).unwrap();
+
+        assert_eq!(names(&queue), ["a", "b"]);
+        assert!(queue.has_playlist(dir.join(&name)));
+    }
+
+    #[test]
+    fn removes_by_path_and_source() {
+        let mut queue = Queue::default();
+        queue.add_song(song("/music/a/one.mp3"));
+        queue.add_song(song("/music/ab/two.mp3"));
+        queue.add_song(song("/other/three.mp3").source(Some("/lists/mix.m3u")));
+
+        // a prefix only matches whole path components
+        assert_eq!(queue.remove_path("/music/a").len(), 1);
+        assert_eq!(names(&queue), ["two", "three"]);
+
+        // paths are compared once made absolute
+        assert_eq!(queue.remove_source("/lists/./mix.m3u").len(), 1);
+        assert_eq!(names(&queue), ["two"]);
     }
 }
@@ -0,0 +1,739 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::cache::{Cache, Dir, Entry, Stamp};
+use crate::config::Config;
+use crate::error::Error;
+use crate::playlist::{self, Format, Playlist};
+use crate::song::Song;
+use crate::tags::Tags;
+use crate::{Message, Source};
+
+/// What was found at one path: a song, a playlist, or the contents of a
+/// folder (but not its subfolders).
+#[derive(Default)]
+pub struct Scanned {
+    /// The folder walked, if the path was one.
+    pub dir: Option<PathBuf>,
+
+    pub songs: Vec<Song>,
+    pub playlists: Vec<(PathBuf, Playlist)>,
+
+    /// How many non-audio files were skipped.
+    pub skipped: usize,
+    pub messages: Vec<Message>,
+}
+
+/// A path to scan, and how it was reached.
+pub struct Job {
+    pub path: PathBuf,
+
+    /// How many folders down from where the scan started this is.
+    depth: usize,
+
+    /// The patterns from `.crampignore` files in the folders above.
+    ignore: Arc<Vec<String>>,
+}
+
+impl Job {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: absolute(path),
+            depth: 0,
+            ignore: Arc::default(),
+        }
+    }
+}
+
+/// What everything scanned in one go shares.
+#[derive(Default)]
+pub struct Shared {
+    /// The folders scanned so far, by their canonical path.
+    visited: Mutex<HashSet<PathBuf>>,
+
+    cache: Option<Mutex<Cache>>,
+}
+
+impl Shared {
+    pub fn with_cache(cache: Cache) -> Self {
+        Self {
+            cache: Some(Mutex::new(cache)),
+            ..Self::default()
+        }
+    }
+}
+
+/// Scans a single path, returning what was found and the subfolders left to
+/// scan.
+///
+/// Files found in folders are only loaded if they look like audio (see
+/// [`loadable`]), but a song given directly always is. Folders already
+/// visited are skipped, so symlink loops end.
+pub fn scan(job: &Job, config: &Config, shared: &Shared) -> (Scanned, Vec<Job>) {
+    let mut scanned = Scanned::default();
+    let path = job.path.as_path();
+
+    if path.is_dir() {
+        let Ok(canonical) = path.canonicalize() else {
+            return (scanned, Vec::new());
+        };
+        if !shared.visited.lock().unwrap().insert(canonical) {
+            return (scanned, Vec::new());
+        }
+        return scan_dir(job, config, shared);
+    } else if !path.is_file() {
+        return (scanned, Vec::new());
+    }
+
+    if Format::from_path(path).is_some() {
+        match read_playlist(path) {
+            Ok(mut playlist) => {
+                read_tags(&mut playlist, shared.cache.as_ref());
+                scanned.playlists.push((path.to_path_buf(), playlist));
+            }
+            Err(e) => scanned.messages.push(e),
+        }
+    } else {
+        match Song::new(path) {
+            Ok(song) => scanned.songs.push(song.tags(Tags::read(path))),
+            Err(e) => scanned.messages.push(e.into()),
+        }
+    }
+
+    (scanned, Vec::new())
+}
+
+fn scan_dir(job: &Job, config: &Config, shared: &Shared) -> (Scanned, Vec<Job>) {
+    let mut scanned = Scanned::default();
+    let mut dirs = Vec::new();
+    let dir = job.path.as_path();
+
+    let stamp = Stamp::of(dir);
+    let cached = shared
+        .cache
+        .as_ref()
+        .zip(stamp)
+        .and_then(|(cache, stamp)| cache.lock().unwrap().get(dir, stamp));
+
+    let mut entries = match cached {
+        Some(cached) => cached.entries,
+        None => match read_dir(dir, &mut scanned.messages) {
+            Ok(entries) => entries,
+            Err(e) => {
+                scanned
+                    .messages
+                    .push(Message::from(Error::io("enumerate", dir, e)).source(Source::Library));
+                return (scanned, dirs);
+            }
+        },
+    };
+    scanned.dir = Some(dir.to_path_buf());
+
+    let has_ignore = entries.iter().any(|entry| entry.name == ".crampignore");
+    let ignore = match has_ignore.then(|| fs::read_to_string(dir.join(".crampignore"))) {
+        Some(Ok(text)) => {
+            let mut ignore = Vec::clone(&job.ignore);
+            ignore.extend(
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+            Arc::new(ignore)
+        }
+        _ => job.ignore.clone(),
+    };
+
+    let mut order: Vec<_> = (0..entries.len())
+        .filter(|i| {
+            let entry = &entries[*i];
+            (!entry.symlink || config.follow_symlinks)
+                && !ignored(Path::new(&entry.name), config, &ignore)
+        })
+        .collect();
+
+    // read cue sheets first, so the files they split up aren't loaded whole
+    order.sort_by_key(|i| Format::from_path(Path::new(&entries[*i].name)) != Some(Format::Cue));
+    let mut split = HashSet::new();
+    for i in order {
+        let entry = &mut entries[i];
+        let path = dir.join(&entry.name);
+
+        if entry.dir {
+            if config.max_depth.is_none_or(|max| job.depth < max) {
+                dirs.push(Job {
+                    path,
+                    depth: job.depth + 1,
+                    ignore: ignore.clone(),
+                });
+            }
+        } else if split.contains(&path) {
+            continue;
+        } else if Format::from_path(&path).is_some() {
+            match read_playlist(&path) {
+                Ok(mut playlist) => {
+                    read_tags(&mut playlist, shared.cache.as_ref());
+                    split.extend(
+                        playlist
+                            .entries
+                            .iter()
+                            .filter(|entry| entry.start.is_some())
+                            .map(|entry| entry.path.clone()),
+                    );
+                    scanned.playlists.push((path, playlist));
+                }
+                Err(e) => scanned.messages.push(e),
+            }
+        } else {
+            entry.refresh(&path);
+            if !is_audio(&path, config, |path| entry.audio(path, sniff)) {
+                scanned.skipped += 1;
+                continue;
+            }
+
+            let tags = entry.tags(&path);
+            match Song::new(path) {
+                Ok(song) => scanned.songs.push(song.tags(tags)),
+                Err(e) => scanned.messages.push(e.into()),
+            }
+        }
+    }
+
+    if let (Some(cache), Some(stamp)) = (&shared.cache, stamp) {
+        cache
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), Dir { stamp, entries });
+    }
+
+    (scanned, dirs)
+}
+
+/// Lists a folder, for caching.
+fn read_dir(dir: &Path, messages: &mut Vec<Message>) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                messages.push(Message::from(Error::io("read", dir, e)).source(Source::Library));
+                continue;
+            }
+        };
+
+        // follows symlinks, unlike the entry's own metadata
+        let Ok(meta) = fs::metadata(entry.path()) else {
+            continue;
+        };
+        entries.push(Entry {
+            name: entry.file_name(),
+            dir: meta.is_dir(),
+            symlink: entry.file_type().is_ok_and(|t| t.is_symlink()),
+            stamp: Stamp::new(&meta),
+            audio: None,
+            tags: None,
+        });
+    }
+    Ok(entries)
+}
+
+/// Whether a file or folder is hidden or matches an ignore pattern, either
+/// from the config or from `patterns`.
+fn ignored(path: &Path, config: &Config, patterns: &[String]) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+
+    (!config.hidden && name.starts_with('.'))
+        || config
+            .ignore
+            .iter()
+            .chain(patterns)
+            .any(|pattern| glob(pattern, name))
+}
+
+/// Matches a name against a pattern, where `*` matches any run of characters
+/// and `?` any one character.
+fn glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<_> = pattern.chars().collect();
+    let name: Vec<_> = name.chars().collect();
+
+    // the last `*` seen, and where in `name` it started matching
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the `*` eat one more character
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Reads and parses the playlist at `path`.
+pub fn read_playlist(path: &Path) -> Result<Playlist, Message> {
+    let Some(format) = Format::from_path(path) else {
+        return Err(Error::Playlist {
+            path: path.to_path_buf(),
+            reason: "unknown playlist format".into(),
+        }
+        .into());
+    };
+
+    let text = playlist::read(path, format)
+        .map_err(|e| Message::from(Error::io("load", path, e)).source(Source::Playlist))?;
+
+    let home = dirs::home_dir().unwrap_or_else(|| "./".into());
+    let base = path.parent().unwrap_or(Path::new(""));
+    Ok(playlist::parse(format, &text, base, &home))
+}
+
+/// Fills in the details of a playlist's songs from their tags, read from the
+/// cache where possible.
+///
+/// Tags win over what the playlist says (e.g. in `#EXTINF`), except for the
+/// tracks of a cue sheet, which share one file with the whole album's tags.
+pub fn read_tags(playlist: &mut Playlist, cache: Option<&Mutex<Cache>>) {
+    for entry in &mut playlist.entries {
+        let cached = cache.and_then(|cache| cache.lock().unwrap().tags(&entry.path));
+        let tags = cached.unwrap_or_else(|| Tags::read(&entry.path));
+
+        if entry.start.is_some() || entry.end.is_some() {
+            entry.title = entry.title.take().or(tags.title);
+            entry.artist = entry.artist.take().or(tags.artist);
+            entry.album = entry.album.take().or(tags.album);
+        } else {
+            entry.title = tags.title.or(entry.title.take());
+            entry.artist = tags.artist.or(entry.artist.take());
+            entry.album = tags.album.or(entry.album.take());
+        }
+    }
+}
+
+/// Makes `path` absolute (without resolving symlinks), so the same file is
+/// always loaded, and can be removed, by the same path.
+pub fn absolute(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether a file or folder found in a folder should be loaded: folders,
+/// playlists and songs with an allowed extension (or, without one, that start
+/// like audio), unless they're ignored.
+///
+/// Only the config's ignore patterns are checked, not `.crampignore` files.
+pub fn loadable(path: &Path, config: &Config) -> bool {
+    if ignored(path, config, &[]) {
+        return false;
+    } else if path.is_dir() || Format::from_path(path).is_some() {
+        return true;
+    }
+
+    is_audio(path, config, sniff)
+}
+
+/// Whether a song has an allowed extension, or has none and `sniff` says it
+/// starts like audio.
+fn is_audio(path: &Path, config: &Config, sniff: impl FnOnce(&Path) -> bool) -> bool {
+    match path.extension() {
+        Some(ext) => config
+            .extensions
+            .contains(&ext.to_string_lossy().to_ascii_lowercase()),
+        None => sniff(path),
+    }
+}
+
+/// Whether a file starts with the signature of an audio format.
+fn sniff(path: &Path) -> bool {
+    let mut header = [0; 12];
+    let Ok(n) = File::open(path).and_then(|mut file| file.read(&mut header)) else {
+        return false;
+    };
+
+    match &header[..n] {
+        [b'I', b'D', b'3', ..] | [b'f', b'L', b'a', b'C', ..] | [b'O', b'g', b'g', b'S', ..] => {
+            true
+        }
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E'] => true,
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => true,
+        [0x1a, 0x45, 0xdf, 0xa3, ..] => true,
+        // an MPEG audio frame
+        [0xff, second, ..] => second & 0xe0 == 0xe0,
+        _ => false,
+    }
+}
+
+/// How far along the current scan is.
+#[derive(Default)]
+pub struct Progress {
+    pub dirs: usize,
+    pub songs: usize,
+    pub skipped: usize,
+}
+
+/// Scans folders on background threads, a folder at a time.
+pub struct Scanner {
+    jobs: Sender<Job>,
+    results: Receiver<Scanned>,
+
+    /// Paths sent to the workers that haven't been scanned yet.
+    pending: Arc<AtomicUsize>,
+
+    shared: Arc<Shared>,
+
+    progress: Option<Progress>,
+}
+
+impl Scanner {
+    pub fn new(config: Arc<Config>, cache: Option<Cache>) -> Self {
+        let shared = match cache {
+            Some(cache) => Shared::with_cache(cache),
+            None => Shared::default(),
+        };
+
+        let (jobs, jobs_rx) = unbounded::<Job>();
+        let (results_tx, results) = unbounded();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let shared = Arc::new(shared);
+
+        let workers = thread::available_parallelism().map_or(4, NonZeroUsize::get);
+        for _ in 0..workers {
+            let config = config.clone();
+            let jobs = jobs.clone();
+            let jobs_rx = jobs_rx.clone();
+            let results_tx = results_tx.clone();
+            let pending = pending.clone();
+            let shared = shared.clone();
+
+            thread::spawn(move || {
+                for job in jobs_rx {
+                    let (scanned, dirs) = scan(&job, &config, &shared);
+
+                    // count the subfolders before this one is done, so the
+                    // scan never looks finished early
+                    pending.fetch_add(dirs.len(), Ordering::SeqCst);
+                    for dir in dirs {
+                        let _ = jobs.send(dir);
+                    }
+
+                    let _ = results_tx.send(scanned);
+                    pending.fetch_sub(1, Ordering::SeqCst);
+                }
+            });
+        }
+
+        Self {
+            jobs,
+            results,
+
+            pending,
+            shared,
+
+            progress: None,
+        }
+    }
+
+    /// Starts scanning a song, playlist or folder.
+    pub fn scan(&mut self, path: impl AsRef<Path>) {
+        let path = absolute(path);
+        if let Some(cache) = &self.shared.cache {
+            cache.lock().unwrap().scanning(&path);
+        }
+
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let _ = self.jobs.send(Job::new(path));
+        self.progress.get_or_insert_with(Progress::default);
+    }
+
+    /// How far along the scan is, if one is running.
+    pub fn progress(&self) -> Option<&Progress> {
+        self.progress.as_ref()
+    }
+
+    /// Returns everything scanned since the last call, and a summary of the
+    /// scan if it's done (saving the cache, if there is one).
+    pub fn poll(&mut self) -> (Vec<Scanned>, Vec<Message>) {
+        // checked first, so nothing scanned after it is missed
+        let done = self.pending.load(Ordering::SeqCst) == 0;
+
+        let results: Vec<_> = self.results.try_iter().collect();
+        let Some(progress) = &mut self.progress else {
+            return (results, Vec::new());
+        };
+
+        for scanned in &results {
+            progress.dirs += scanned.dir.is_some() as usize;
+            progress.songs += scanned.songs.len()
+                + scanned
+                    .playlists
+                    .iter()
+                    .map(|(_, playlist)| playlist.entries.len())
+                    .sum::<usize>();
+            progress.skipped += scanned.skipped;
+        }
+
+        if !done {
+            return (results, Vec::new());
+        }
+
+        let summary = if progress.skipped > 0 {
+            format!(
+                "scanned {} folders: {} songs, skipped {} non-audio files",
+                progress.dirs, progress.songs, progress.skipped
+            )
+        } else {
+            format!(
+                "scanned {} folders: {} songs",
+                progress.dirs, progress.songs
+            )
+        };
+        self.progress = None;
+        self.shared.visited.lock().unwrap().clear();
+
+        let mut messages = vec![Message::new(summary).source(Source::Library)];
+        if let Some(cache) = &self.shared.cache {
+            if let Err(e) = cache.lock().unwrap().save() {
+                let path = Cache::path().unwrap_or_default();
+                let error = Error::io("save library cache", path, e);
+                messages.push(Message::from(error).source(Source::Library));
+            }
+        }
+        (results, messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A folder for a test to put files in, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("cramp-scanner-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str, contents: &[u8]) -> PathBuf {
+            let path = self.0.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn sniffs_audio() {
+        let dir = TempDir::new("sniff");
+        for (name, header) in [
+            ("id3", &b"ID3\x04\0\0"[..]),
+            ("flac", b"fLaC\0\0\0\x22"),
+            ("ogg", b"OggS\0\x02"),
+            ("wav", b"RIFF\x24\0\0\0WAVEfmt "),
+            ("m4a", b"\0\0\0\x20ftypM4A "),
+            ("mkv", b"\x1a\x45\xdf\xa3\x01"),
+            ("mpeg", b"\xff\xfb\x90\x64"),
+        ] {
+            assert!(sniff(&dir.file(name, header)), "{name}");
+        }
+
+        for (name, header) in [
+            ("text", &b"hello, world"[..]),
+            ("riff", b"RIFF\x24\0\0\0AVI "),
+            ("empty", b""),
+            ("ff", b"\xff\x00"),
+        ] {
+            assert!(!sniff(&dir.file(name, header)), "{name}");
+        }
+        assert!(!sniff(&dir.0.join("missing")));
+    }
+
+    #[test]
+    fn checks_extensions_before_sniffing() {
+        let config = Config::default();
+        let never = |_: &Path| panic!("sniffed a file with an extension");
+        assert!(is_audio(Path::new("a.MP3"), &config, never));
+        assert!(!is_audio(Path::new("a.txt"), &config, never));
+
+        assert!(is_audio(Path::new("a"), &config, |_| true));
+        assert!(!is_audio(Path::new("a"), &config, |_| false));
+    }
+
+    #[test]
+    fn skips_non_audio_files() {
+        let dir = TempDir::new("skip");
+        dir.file("song.mp3", b"");
+        dir.file("cover.jpg", b"");
+        dir.file("notes", b"just some notes");
+        dir.file("track", b"fLaC\0\0\0\x22");
+
+        let (scanned, _) = scan(&Job::new(&dir.0), &Config::default(), &Shared::default());
+        let mut names: Vec<_> = scanned
+            .songs
+            .iter()
+            .map(|song| song.name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["song", "track"]);
+        assert_eq!(scanned.skipped, 2);
+    }
+
+    /// Scans `dir` and everything under it, returning the names of the songs
+    /// found.
+    fn scan_all(dir: &Path, config: &Config) -> Vec<String> {
+        let shared = Shared::default();
+        let mut names = Vec::new();
+        let mut jobs = vec![Job::new(dir)];
+        while let Some(job) = jobs.pop() {
+            let (scanned, dirs) = scan(&job, config, &shared);
+            names.extend(scanned.songs.into_iter().map(|song| song.name));
+            jobs.extend(dirs);
+        }
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn prefers_tags_to_playlists() {
+        let dir = TempDir::new("playlist-tags");
+        crate::tags::tests::wav(&dir.0.join("song.wav"), &[(b"INAM", "Tagged")]);
+        let list = dir.file(
+            "list.m3u",
+            b"#EXTINF:-1,Singer - Listed\nsong.wav\n#EXTINF:-1,Other\nmissing.wav\n",
+        );
+        let cue = dir.file(
+            "album.cue",
+            b"FILE \"song.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n",
+        );
+
+        let Ok(mut playlist) = read_playlist(&list) else {
+            panic!("couldn't read the playlist");
+        };
+        read_tags(&mut playlist, None);
+        let entries = &playlist.entries;
+        // the tags have no artist, so the playlist's stands
+        assert_eq!(entries[0].title.as_deref(), Some("Tagged"));
+        assert_eq!(entries[0].artist.as_deref(), Some("Singer"));
+        assert_eq!(entries[1].title.as_deref(), Some("Other"));
+
+        // the whole file's tags only fill in for a track's
+        let Ok(mut playlist) = read_playlist(&cue) else {
+            panic!("couldn't read the cue sheet");
+        };
+        playlist.entries[0].title = Some("Track".into());
+        read_tags(&mut playlist, None);
+        assert_eq!(playlist.entries[0].title.as_deref(), Some("Track"));
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob("*.log", "scan.log"));
+        assert!(glob("*.log", ".log"));
+        assert!(!glob("*.log", "scan.logs"));
+        assert!(glob("a*b*c", "abbbc"));
+        assert!(glob("a*b*c", "aXbYbZc"));
+        assert!(!glob("a*b*c", "aXbY"));
+        assert!(glob("track??", "track01"));
+        assert!(!glob("track??", "track1"));
+        assert!(glob("*", ""));
+        assert!(glob("**x", "x"));
+        assert!(glob("exact", "exact"));
+        assert!(!glob("exact", "exactly"));
+    }
+
+    #[test]
+    fn ignores_hidden_and_matching_names() {
+        let mut config = Config {
+            ignore: vec!["*.bak".into()],
+            ..Config::default()
+        };
+        let patterns = ["demos".to_string()];
+
+        assert!(ignored(Path::new("/music/.hidden"), &config, &[]));
+        assert!(ignored(Path::new("/music/a.bak"), &config, &[]));
+        assert!(ignored(Path::new("/music/demos"), &config, &patterns));
+        assert!(!ignored(Path::new("/music/demos"), &config, &[]));
+        assert!(!ignored(Path::new("/music/song.mp3"), &config, &patterns));
+
+        config.hidden = true;
+        assert!(!ignored(Path::new("/music/.hidden"), &config, &[]));
+    }
+
+    #[test]
+    fn applies_ignore_files_below_them() {
+        let dir = TempDir::new("ignore");
+        dir.file("a/.crampignore", b"# live takes\nlive*\n");
+        dir.file("a/live one.mp3", b"");
+        dir.file("a/b/live two.mp3", b"");
+        dir.file("a/studio.mp3", b"");
+        dir.file("live three.mp3", b"");
+        dir.file(".hidden/song.mp3", b"");
+
+        assert_eq!(
+            scan_all(&dir.0, &Config::default()),
+            ["live three", "studio"]
+        );
+    }
+
+    #[test]
+    fn limits_depth() {
+        let dir = TempDir::new("depth");
+        dir.file("0.mp3", b"");
+        dir.file("a/1.mp3", b"");
+        dir.file("a/b/2.mp3", b"");
+
+        let mut config = Config {
+            max_depth: Some(1),
+            ..Config::default()
+        };
+        assert_eq!(scan_all(&dir.0, &config), ["0", "1"]);
+        config.max_depth = Some(0);
+        assert_eq!(scan_all(&dir.0, &config), ["0"]);
+    }
+
+    #[test]
+    fn survives_symlink_loops() {
+        let dir = TempDir::new("loop");
+        dir.file("a/song.mp3", b"");
+        std::os::unix::fs::symlink(&dir.0, dir.0.join("a/up")).unwrap();
+
+        let mut config = Config::default();
+        assert_eq!(scan_all(&dir.0, &config), ["song"]);
+        config.follow_symlinks = false;
+        assert_eq!(scan_all(&dir.0, &config), ["song"]);
+    }
+}
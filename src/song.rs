@@ -2,30 +2,66 @@ use std::path::PathBuf;
 
 use kittyaudio::{KaError, Sound};
 
+use crate::error::Error;
+use crate::tags::Tags;
+
+#[derive(Clone)]
 pub struct Song {
     pub name: String,
     pub path: PathBuf,
+
+    /// The normalized path used for searching.
+    pub search_key: String,
+
+    pub artist: Option<String>,
+    pub album: Option<String>,
+
+    /// Where in the file the song starts and ends, in seconds, for songs that
+    /// share a file (e.g. from a cue sheet).
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+
     pub next: Option<PathBuf>,
     pub no_shuffle: bool,
     pub user_added: bool,
+
+    /// The playlist the song was loaded from, if any.
+    pub source: Option<PathBuf>,
+
+    /// Why the song couldn't be played, if it couldn't.
+    pub broken: Option<String>,
 }
 
 impl Song {
-    pub fn new(path: impl Into<PathBuf>) -> Self {
+    /// Creates a song from the file at `path`, which has to have a name.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, Error> {
         let path = path.into();
-        let name = path
-            .file_stem()
-            .expect("attempted to load non-file as song")
-            .to_string_lossy()
-            .to_string();
+        let Some(name) = path.file_stem() else {
+            return Err(Error::NotASong(path));
+        };
+        let name = name.to_string_lossy().to_string();
+
+        let mut search_key = path.display().to_string();
+        search_key.make_ascii_lowercase();
 
-        Self {
+        Ok(Self {
             name,
             path,
+            search_key,
+
+            artist: None,
+            album: None,
+
+            start: None,
+            end: None,
+
             next: None,
             no_shuffle: false,
             user_added: true,
-        }
+
+            source: None,
+            broken: None,
+        })
     }
 
     pub fn next(mut self, next: Option<impl Into<PathBuf>>) -> Self {
@@ -38,7 +74,68 @@ impl Song {
         self
     }
 
+    /// Overrides the name derived from the path, if given one.
+    pub fn title(mut self, title: Option<String>) -> Self {
+        if let Some(title) = title {
+            self.name = title;
+        }
+        self
+    }
+
+    pub fn artist(mut self, artist: Option<String>) -> Self {
+        self.artist = artist;
+        self
+    }
+
+    pub fn album(mut self, album: Option<String>) -> Self {
+        self.album = album;
+        self
+    }
+
+    /// Takes the title, artist and album from the file's tags.
+    pub fn tags(self, tags: Tags) -> Self {
+        self.title(tags.title).artist(tags.artist).album(tags.album)
+    }
+
+    pub fn source(mut self, source: Option<impl Into<PathBuf>>) -> Self {
+        self.source = source.map(Into::into);
+        self
+    }
+
+    pub fn bounds(mut self, start: Option<f64>, end: Option<f64>) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
     pub fn load(&self) -> Result<Sound, KaError> {
         Sound::from_path(&self.path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn needs_a_file_name() {
+        assert!(matches!(Song::new("/"), Err(Error::NotASong(_))));
+        assert!(matches!(Song::new(".."), Err(Error::NotASong(_))));
+    }
+
+    #[test]
+    fn names_songs_by_their_file() {
+        let song = Song::new("/Music/Band - Song.MP3").unwrap();
+        assert_eq!(song.name, "Band - Song");
+        assert_eq!(song.search_key, "/music/band - song.mp3");
+        assert!(song.user_added);
+
+        // missing tags leave the name alone
+        let song = song.tags(Tags {
+            artist: Some("Band".into()),
+            ..Tags::default()
+        });
+        assert_eq!(song.name, "Band - Song");
+        assert_eq!(song.artist.as_deref(), Some("Band"));
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::path::Path;
+
+use kittyaudio::symphonia::core::formats::FormatOptions;
+use kittyaudio::symphonia::core::io::MediaSourceStream;
+use kittyaudio::symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use kittyaudio::symphonia::core::probe::Hint;
+
+/// The metadata read from an audio file's tags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl Tags {
+    /// Reads the tags of the file at `path`, leaving out any it doesn't have
+    /// (or all of them, if it can't be read).
+    pub fn read(path: &Path) -> Self {
+        let mut tags = Self::default();
+        let Ok(file) = File::open(path) else {
+            return tags;
+        };
+
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(ext);
+        }
+
+        let source = MediaSourceStream::new(Box::new(file), Default::default());
+        let Ok(mut probed) = kittyaudio::symphonia::default::get_probe().format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        ) else {
+            return tags;
+        };
+
+        // tags in the container win over ones in front of it (e.g. ID3)
+        if let Some(revision) = probed.format.metadata().current() {
+            tags.fill(revision);
+        }
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+            tags.fill(revision);
+        }
+        tags
+    }
+
+    /// Sets the tags not set yet from a metadata revision.
+    fn fill(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                _ => continue,
+            };
+
+            // some formats (e.g. RIFF) pad their strings with NULs
+            let value = tag.value.to_string();
+            let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+            if field.is_none() && !value.is_empty() {
+                *field = Some(value.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Writes a short silent WAV file with a RIFF `INFO` list of tags.
+    pub fn wav(path: &Path, info: &[(&[u8; 4], &str)]) {
+        let mut list = b"INFO".to_vec();
+        for (id, value) in info {
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            if value.len() % 2 == 1 {
+                value.push(0);
+            }
+            list.extend_from_slice(*id);
+            list.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            list.extend_from_slice(&value);
+        }
+
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        fmt.extend_from_slice(&1u16.to_le_bytes()); // mono
+        fmt.extend_from_slice(&8000u32.to_le_bytes());
+        fmt.extend_from_slice(&16000u32.to_le_bytes());
+        fmt.extend_from_slice(&2u16.to_le_bytes());
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+
+        let mut body = b"WAVE".to_vec();
+        for (id, chunk) in [(b"fmt ", fmt), (b"LIST", list), (b"data", vec![0; 800])] {
+            body.extend_from_slice(id);
+            body.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            body.extend_from_slice(&chunk);
+        }
+
+        let mut file = b"RIFF".to_vec();
+        file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        file.extend_from_slice(&body);
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn reads_tags() {
+        let dir = std::env::temp_dir().join(format!("cramp-tags-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song.wav");
+        wav(
+            &path,
+            &[(b"INAM", "Title"), (b"IART", "Artist"), (b"IPRD", "Album")],
+        );
+
+        let tags = Tags::read(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            tags,
+            Tags {
+                title: Some("Title".into()),
+                artist: Some("Artist".into()),
+                album: Some("Album".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn unreadable_files_have_no_tags() {
+        assert_eq!(Tags::read(Path::new("/nonexistent.mp3")), Tags::default());
+    }
+}
@@ -1,7 +1,13 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
 use std::sync::mpsc::{channel, Receiver};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
+use chrono::{DateTime, Local};
+
 // use async_std::channel::{unbounded, Receiver};
 use async_std::sync::Mutex;
 use cod::{
@@ -9,11 +15,26 @@ use cod::{
     read::{KeyCode, KeyEvent, KeyModifiers},
 };
 
+use crate::browser::{Browser, Entry, Grouping};
+use crate::config::Config;
+use crate::editor::{self, Editor};
+use crate::error::Severity;
+use crate::log::Log;
 use crate::player::Player;
 use crate::queue::{Key, Queue};
+use crate::scanner::Progress;
 use crate::song::Song;
 use crate::Message;
 
+/// How long messages stay at the bottom of the screen, before they're only in
+/// the log view.
+const NOTICE_TIME: Duration = Duration::from_secs(5);
+/// Likewise for warnings and errors.
+const ERROR_TIME: Duration = Duration::from_secs(15);
+/// How many messages are shown at the bottom at once; the rest are left to
+/// the log view.
+const MAX_NOTICES: usize = 3;
+
 pub enum Event {
     Exit,
     Shuffle,
@@ -26,12 +47,196 @@ pub enum Event {
     PlayNext(Key),
     Queue(Key),
 
+    PlayAll(Vec<Key>),
+    PlayNextAll(Vec<Key>),
+    QueueAll(Vec<Key>),
+
     SeekRight,
     SeekLeft,
+
+    Save(Export, PathBuf),
+
+    Remove(Vec<Key>),
+    Unload(PathBuf),
+    Open(PathBuf),
+}
+
+/// What to save to a playlist.
+pub enum Export {
+    Queue,
+    History,
+    Songs(Vec<Key>),
+}
+
+/// A line of text input, shown in place of the list header.
+struct Prompt {
+    kind: PromptKind,
+    text: String,
+    idx: usize,
+}
+
+enum PromptKind {
+    Save(Export),
+    SavePlaylist,
+    OpenPlaylist,
+    PlaylistTitle,
+    Unload,
+    Open,
+}
+
+impl Prompt {
+    fn new(kind: PromptKind) -> Self {
+        Self::with_text(kind, String::new())
+    }
+
+    fn with_text(kind: PromptKind, text: String) -> Self {
+        Self {
+            kind,
+            idx: text.len(),
+            text,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self.kind {
+            PromptKind::Save(Export::Queue) => "save queue as: ",
+            PromptKind::Save(Export::History) => "save history as: ",
+            PromptKind::Save(Export::Songs(_)) => "save marked as: ",
+            PromptKind::SavePlaylist => "save playlist as: ",
+            PromptKind::OpenPlaylist => "open playlist: ",
+            PromptKind::PlaylistTitle => "playlist title: ",
+            PromptKind::Unload => "unload playlist or folder: ",
+            PromptKind::Open => "open: ",
+        }
+    }
+
+    /// Handles a key, returning whether it was the last one (`Enter` or `Esc`).
+    fn key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char(ch) => {
+                self.text.insert(self.idx, ch);
+                self.idx += ch.len_utf8();
+            }
+            KeyCode::Backspace => {
+                if let Some(ch) = self.text[..self.idx].chars().next_back() {
+                    self.idx -= ch.len_utf8();
+                    self.text.remove(self.idx);
+                }
+            }
+            KeyCode::Left => {
+                if let Some(ch) = self.text[..self.idx].chars().next_back() {
+                    self.idx -= ch.len_utf8();
+                }
+            }
+            KeyCode::Right => {
+                if let Some(ch) = self.text[self.idx..].chars().next() {
+                    self.idx += ch.len_utf8();
+                }
+            }
+            KeyCode::Enter | KeyCode::Esc => return true,
+            _ => {}
+        }
+
+        false
+    }
+
+    /// The path entered, with `~/` expanded.
+    fn path(&self) -> Option<PathBuf> {
+        let text = self.text.trim();
+        (!text.is_empty()).then(|| expand_home(text))
+    }
+
+    /// Completes the file name being typed as far as possible, returning the
+    /// candidates if there's more than one.
+    fn complete(&mut self) -> Vec<String> {
+        let (dir, partial) = match self.text.rfind('/') {
+            Some(idx) => self.text.split_at(idx + 1),
+            None => ("", self.text.as_str()),
+        };
+
+        let read = if dir.is_empty() {
+            fs::read_dir(".")
+        } else {
+            fs::read_dir(expand_home(dir))
+        };
+        let Ok(read) = read else {
+            return Vec::new();
+        };
+
+        let mut names: Vec<_> = read
+            .flatten()
+            .filter_map(|entry| {
+                let mut name = entry.file_name().into_string().ok()?;
+                if !name.starts_with(partial)
+                    || (name.starts_with('.') && !partial.starts_with('.'))
+                {
+                    return None;
+                }
+
+                if entry.path().is_dir() {
+                    name.push('/');
+                }
+                Some(name)
+            })
+            .collect();
+        names.sort();
+
+        let Some(first) = names.first() else {
+            return names;
+        };
+
+        let common = names.iter().fold(first.as_str(), |common, name| {
+            let len = common
+                .char_indices()
+                .zip(name.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(common.len().min(name.len()), |((i, _), _)| i);
+            &common[..len]
+        });
+
+        self.text = format!("{dir}{common}");
+        self.idx = self.text.len();
+
+        if names.len() == 1 {
+            names.clear();
+        }
+        names
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Songs,
+    Browser,
+    Editor,
+    Failures,
+    Log,
 }
 
 pub struct Ui {
-    messages: Vec<Message>,
+    log: Log,
+
+    /// The least severe messages shown, at the bottom and in the log view.
+    min_severity: Severity,
+
+    /// The selected message in the log view, counting back from the latest.
+    log_idx: usize,
+    /// Whether to show the details of the selected message.
+    log_detail: bool,
+
+    /// A line shown at the bottom until the next key, that isn't logged.
+    hint: Option<String>,
+
+    /// The progress of a library scan, if one is running.
+    progress: Option<String>,
+
+    view: View,
+    browser: Browser,
+    editor: Option<Editor>,
+
+    /// The scroll position and selection in the list of unplayable songs.
+    failures: usize,
+    failure_idx: usize,
 
     songs: usize,
     song_idx: usize,
@@ -39,12 +244,24 @@ pub struct Ui {
     search: Option<String>,
     search_idx: usize,
 
+    /// The songs matching `search`, cached until the query or library changes.
+    filtered: Vec<Key>,
+    filtered_query: Option<String>,
+    filtered_gen: Option<usize>,
+
+    prompt: Option<Prompt>,
+
+    /// The songs marked for bulk actions.
+    marked: HashSet<Key>,
+    /// The last song (un)marked, where range selections start.
+    mark_anchor: Option<Key>,
+
     rx: Mutex<Receiver<KeyEvent>>,
     _handle: JoinHandle<()>,
 }
 
 impl Ui {
-    pub async fn new() -> Self {
+    pub async fn new(config: &Config) -> Self {
         term::secondary_screen();
         term::enable_raw_mode();
         clear::all();
@@ -58,7 +275,19 @@ impl Ui {
         });
 
         Self {
-            messages: Vec::with_capacity(4),
+            log: Log::new(config.log_file.as_deref()),
+            min_severity: config.messages,
+            log_idx: 0,
+            log_detail: false,
+            hint: None,
+            progress: None,
+
+            view: View::Songs,
+            browser: Browser::new(),
+            editor: None,
+
+            failures: 0,
+            failure_idx: 0,
 
             songs: 0,
             song_idx: 0,
@@ -66,6 +295,15 @@ impl Ui {
             search: None,
             search_idx: 0,
 
+            filtered: Vec::new(),
+            filtered_query: None,
+            filtered_gen: None,
+
+            prompt: None,
+
+            marked: HashSet::new(),
+            mark_anchor: None,
+
             rx: Mutex::new(rx),
             _handle,
         }
@@ -78,13 +316,71 @@ impl Ui {
             .await
             .recv_timeout(Duration::from_millis(10))
             .ok()?;
-        self.messages.clear();
+        self.hint = None;
+
+        if let Some(prompt) = &mut self.prompt {
+            if key.code == KeyCode::Tab && !matches!(prompt.kind, PromptKind::PlaylistTitle) {
+                let mut candidates = prompt.complete();
+                if candidates.len() > 8 {
+                    candidates.truncate(8);
+                    candidates.push("...".into());
+                }
+                if !candidates.is_empty() {
+                    self.hint = Some(candidates.join("  "));
+                }
+                return None;
+            }
+
+            if !prompt.key(key.code) {
+                return None;
+            }
+
+            let prompt = self.prompt.take()?;
+            if key.code == KeyCode::Esc {
+                return None;
+            }
+
+            if let PromptKind::PlaylistTitle = prompt.kind {
+                if let Some(editor) = &mut self.editor {
+                    let title = prompt.text.trim();
+                    editor.title = (!title.is_empty()).then(|| title.to_string());
+                    editor.modified = true;
+                }
+                return None;
+            }
+
+            let path = prompt.path()?;
+            return match prompt.kind {
+                PromptKind::Save(export) => Some(Event::Save(export, m3u_path(path))),
+                PromptKind::SavePlaylist => {
+                    if let Some(editor) = &mut self.editor {
+                        let message = editor.save(m3u_path(path));
+                        self.add_message(message);
+                    }
+                    None
+                }
+                PromptKind::OpenPlaylist => {
+                    match Editor::open(path) {
+                        Ok(editor) => {
+                            self.editor = Some(editor);
+                            self.view = View::Editor;
+                        }
+                        Err(message) => self.add_message(message),
+                    }
+                    None
+                }
+                PromptKind::Unload => Some(Event::Unload(path)),
+                PromptKind::Open => Some(Event::Open(path)),
+                PromptKind::PlaylistTitle => None,
+            };
+        }
 
         'search: {
             if key.modifiers.contains(KeyModifiers::CONTROL) {
                 break 'search;
             }
 
+            let previous = self.selected(queue);
             if let Some(search) = &mut self.search {
                 match key.code {
                     KeyCode::Char(ch) => {
@@ -109,30 +405,43 @@ impl Ui {
                     _ => break 'search,
                 }
 
-                // TODO: adjust selection, don't reset it
-                self.songs = 0;
-                self.song_idx = 0;
+                self.reselect(queue, previous);
+                return None;
+            }
+        }
+
+        if self.view == View::Browser {
+            if let Some(event) = self.browser_event(key, queue) {
+                return event;
+            }
+        }
+
+        if self.view == View::Editor {
+            if let Some(event) = self.editor_event(key).await {
+                return event;
+            }
 
+            // the song list's keys would act on songs that aren't shown
+            if !is_global(key.code) {
                 return None;
             }
         }
 
+        if self.view == View::Failures {
+            if let Some(event) = self.failures_event(key, queue) {
+                return event;
+            }
+        }
+
+        if self.view == View::Log && self.log_event(key) {
+            return None;
+        }
+
         match key.code {
             KeyCode::Char('q') => {
-                self.clear();
-                let (w, _) = term::size_or();
-                draw_centered(3, "do you want to exit?", None, w, true);
-                draw_centered(4, "press y to exit", None, w, false);
-                self.flush();
-
                 if self
-                    .rx
-                    .lock()
+                    .confirm("do you want to exit?", "press y to exit")
                     .await
-                    .recv_timeout(Duration::from_secs(2))
-                    .ok()?
-                    .code
-                    == KeyCode::Char('y')
                 {
                     Some(Event::Exit)
                 } else {
@@ -156,7 +465,48 @@ impl Ui {
             }
             KeyCode::Char(' ') => Some(Event::PlayPause),
 
+            KeyCode::Char('b') => {
+                self.view = View::Browser;
+                None
+            }
+            KeyCode::Char('F') => {
+                self.view = View::Failures;
+                None
+            }
+            KeyCode::Char('L') => {
+                self.log_idx = 0;
+                self.view = View::Log;
+                None
+            }
+            KeyCode::Char('V') => {
+                self.min_severity = match self.min_severity {
+                    Severity::Info => Severity::Warning,
+                    Severity::Warning => Severity::Error,
+                    Severity::Error => Severity::Info,
+                };
+                self.hint = Some(format!(
+                    "showing {} messages and up",
+                    self.min_severity.name()
+                ));
+                None
+            }
+            KeyCode::Char('E') => {
+                self.editor.get_or_insert_with(Editor::new);
+                self.view = View::Editor;
+                None
+            }
+            KeyCode::Char('e') => {
+                let songs = if self.marked.is_empty() {
+                    self.selected(queue).into_iter().collect()
+                } else {
+                    self.marked_songs(queue)
+                };
+                self.add_to_playlist(queue, &songs);
+                None
+            }
+
             KeyCode::Char('/') => {
+                self.view = View::Songs;
                 if self.search.is_none() {
                     self.search = Some(String::with_capacity(16));
                 }
@@ -164,9 +514,12 @@ impl Ui {
             }
 
             KeyCode::Down => {
-                self.song_idx += 1;
-                if self.song_idx > 5 {
-                    self.songs += 1;
+                self.refresh_filter(queue);
+                if self.song_idx + 1 < self.filtered.len() {
+                    self.song_idx += 1;
+                    if self.song_idx > 5 {
+                        self.songs += 1;
+                    }
                 }
                 None
             }
@@ -178,23 +531,300 @@ impl Ui {
                 None
             }
 
-            KeyCode::Enter => filter_songs(queue.songs(), &self.search)
-                .nth(self.song_idx)
-                .map(|(id, _)| Event::PlayNow(id)),
-            KeyCode::Char('n') => filter_songs(queue.songs(), &self.search)
-                .nth(self.song_idx)
-                .map(|(id, _)| Event::PlayNext(id)),
-            KeyCode::Char('a') => filter_songs(queue.songs(), &self.search)
-                .nth(self.song_idx)
-                .map(|(id, _)| Event::Queue(id)),
+            KeyCode::Enter => self.selected(queue).map(Event::PlayNow),
+            KeyCode::Char('n') if !self.marked.is_empty() => {
+                Some(Event::PlayNextAll(self.marked_songs(queue)))
+            }
+            KeyCode::Char('n') => self.selected(queue).map(Event::PlayNext),
+            KeyCode::Char('a') if !self.marked.is_empty() => {
+                Some(Event::QueueAll(self.marked_songs(queue)))
+            }
+            KeyCode::Char('a') => self.selected(queue).map(Event::Queue),
+
+            KeyCode::Char('P') => self.matching(queue).map(Event::PlayAll),
+            KeyCode::Char('N') => self.matching(queue).map(Event::PlayNextAll),
+            KeyCode::Char('A') => self.matching(queue).map(Event::QueueAll),
+
+            KeyCode::Char('w') => {
+                self.prompt = Some(Prompt::new(PromptKind::Save(Export::Queue)));
+                None
+            }
+            KeyCode::Char('W') => {
+                if self.marked.is_empty() {
+                    self.add_message(Message::stc("no songs are marked"));
+                } else {
+                    let songs = self.marked_songs(queue);
+                    self.prompt = Some(Prompt::new(PromptKind::Save(Export::Songs(songs))));
+                }
+                None
+            }
+            KeyCode::Char('H') => {
+                self.prompt = Some(Prompt::new(PromptKind::Save(Export::History)));
+                None
+            }
+
+            KeyCode::Char('D') if !self.marked.is_empty() => {
+                Some(Event::Remove(self.marked_songs(queue)))
+            }
+            KeyCode::Char('D') => self.selected(queue).map(|id| Event::Remove(vec![id])),
+            KeyCode::Char('o') => {
+                self.prompt = Some(Prompt::new(PromptKind::Open));
+                None
+            }
+            KeyCode::Char('U') => {
+                self.prompt = Some(Prompt::new(PromptKind::Unload));
+                None
+            }
+
+            KeyCode::Char('m') => {
+                if let Some(id) = self.selected(queue) {
+                    if !self.marked.remove(&id) {
+                        self.marked.insert(id);
+                    }
+                    self.mark_anchor = Some(id);
+                }
+                None
+            }
+            KeyCode::Char('M') => {
+                self.mark_range(queue);
+                None
+            }
+            KeyCode::Char('*') => {
+                self.refresh_filter(queue);
+                self.marked.extend(self.filtered.iter().copied());
+                None
+            }
+            KeyCode::Char('i') => {
+                self.refresh_filter(queue);
+                for id in &self.filtered {
+                    if !self.marked.remove(id) {
+                        self.marked.insert(*id);
+                    }
+                }
+                None
+            }
+            KeyCode::Char('u') => {
+                self.marked.clear();
+                self.mark_anchor = None;
+                None
+            }
 
             _ => None,
         }
     }
 
+    /// Handles the keys specific to the library browser, returning `None` if
+    /// the key wasn't handled.
+    fn browser_event(&mut self, key: KeyEvent, queue: &Queue) -> Option<Option<Event>> {
+        self.browser.refresh(queue);
+
+        Some(match key.code {
+            KeyCode::Down => {
+                self.browser.down();
+                None
+            }
+            KeyCode::Up => {
+                self.browser.up();
+                None
+            }
+            KeyCode::Enter => self.browser.enter().map(Event::PlayNow),
+            KeyCode::Backspace => {
+                self.browser.leave();
+                None
+            }
+            KeyCode::Tab => {
+                self.browser.toggle_grouping();
+                None
+            }
+
+            KeyCode::Char('p') => {
+                let songs = self.browser.selected_songs();
+                (!songs.is_empty()).then_some(Event::PlayAll(songs))
+            }
+            KeyCode::Char('a') => {
+                let songs = self.browser.selected_songs();
+                (!songs.is_empty()).then_some(Event::QueueAll(songs))
+            }
+            KeyCode::Char('n') => {
+                let songs = self.browser.selected_songs();
+                (!songs.is_empty()).then_some(Event::PlayNextAll(songs))
+            }
+
+            KeyCode::Char('e') => {
+                let songs = self.browser.selected_songs();
+                self.add_to_playlist(queue, &songs);
+                None
+            }
+            KeyCode::Char('D') => {
+                let songs = self.browser.selected_songs();
+                (!songs.is_empty()).then_some(Event::Remove(songs))
+            }
+
+            KeyCode::Char('b') | KeyCode::Esc => {
+                self.view = View::Songs;
+                None
+            }
+
+            _ => return None,
+        })
+    }
+
+    /// Handles the keys specific to the list of unplayable songs, returning
+    /// `None` if the key wasn't handled.
+    fn failures_event(&mut self, key: KeyEvent, queue: &Queue) -> Option<Option<Event>> {
+        let broken: Vec<_> = queue.broken().map(|(id, _)| id).collect();
+        if self.failure_idx >= broken.len() {
+            self.failure_idx = broken.len().saturating_sub(1);
+            self.failures = self.failures.min(self.failure_idx);
+        }
+
+        Some(match key.code {
+            KeyCode::Down => {
+                if self.failure_idx + 1 < broken.len() {
+                    self.failure_idx += 1;
+                    if self.failure_idx > 5 {
+                        self.failures += 1;
+                    }
+                }
+                None
+            }
+            KeyCode::Up => {
+                self.failure_idx = self.failure_idx.saturating_sub(1);
+                if self.failure_idx < self.failures {
+                    self.failures = self.failure_idx;
+                }
+                None
+            }
+            KeyCode::Enter => broken.get(self.failure_idx).copied().map(Event::PlayNow),
+            KeyCode::Char('D') => broken
+                .get(self.failure_idx)
+                .map(|id| Event::Remove(vec![*id])),
+
+            KeyCode::Char('F') | KeyCode::Esc => {
+                self.view = View::Songs;
+                None
+            }
+
+            _ => return None,
+        })
+    }
+
+    /// Handles the keys specific to the log view, returning whether the key
+    /// was handled.
+    fn log_event(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            // clamped to the length of the log when drawn
+            KeyCode::Up => self.log_idx += 1,
+            KeyCode::Down => self.log_idx = self.log_idx.saturating_sub(1),
+            KeyCode::PageUp => self.log_idx += 10,
+            KeyCode::PageDown => self.log_idx = self.log_idx.saturating_sub(10),
+            KeyCode::Enter => self.log_detail = !self.log_detail,
+            KeyCode::Char('L') | KeyCode::Esc => self.view = View::Songs,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handles the keys specific to the playlist editor, returning `None` if
+    /// the key wasn't handled.
+    async fn editor_event(&mut self, key: KeyEvent) -> Option<Option<Event>> {
+        let editor = self.editor.get_or_insert_with(Editor::new);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+        match key.code {
+            KeyCode::Down if shift => editor.move_down(),
+            KeyCode::Up if shift => editor.move_up(),
+            KeyCode::Down => editor.down(),
+            KeyCode::Up => editor.up(),
+            KeyCode::Char('d') | KeyCode::Delete => editor.remove(),
+            KeyCode::Char('x') => editor.toggle_no_shuffle(),
+            KeyCode::Char('t') => editor.toggle_next(),
+
+            KeyCode::Char('w') => {
+                if let Some(path) = editor.path.clone() {
+                    let message = editor.save(path);
+                    self.add_message(message);
+                } else {
+                    self.prompt = Some(Prompt::new(PromptKind::SavePlaylist));
+                }
+            }
+            KeyCode::Char('W') => {
+                let path = editor
+                    .path
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default();
+                self.prompt = Some(Prompt::with_text(PromptKind::SavePlaylist, path));
+            }
+            KeyCode::Char('r') => {
+                let title = editor.title.clone().unwrap_or_default();
+                self.prompt = Some(Prompt::with_text(PromptKind::PlaylistTitle, title));
+            }
+            KeyCode::Char('o') => {
+                if self.discard_playlist().await {
+                    self.prompt = Some(Prompt::new(PromptKind::OpenPlaylist));
+                }
+            }
+            KeyCode::Char('c') => {
+                if self.discard_playlist().await {
+                    self.editor = Some(Editor::new());
+                }
+            }
+
+            KeyCode::Char('E') | KeyCode::Esc => self.view = View::Songs,
+
+            _ => return None,
+        }
+
+        Some(None)
+    }
+
+    /// Adds songs to the playlist being edited, starting a new one if needed.
+    fn add_to_playlist(&mut self, queue: &Queue, songs: &[Key]) {
+        let editor = self.editor.get_or_insert_with(Editor::new);
+        let added = editor.add(songs.iter().filter_map(|id| queue.get(*id)));
+
+        if added == 0 {
+            self.hint = Some("no songs to add to the playlist".to_string());
+        } else {
+            let message = format!("added {added} songs to {}", editor.name());
+            self.add_message(Message::new(message));
+        }
+    }
+
+    /// Checks whether it's okay to throw away the playlist being edited.
+    async fn discard_playlist(&mut self) -> bool {
+        !self.editor.as_ref().is_some_and(|e| e.modified)
+            || self
+                .confirm(
+                    "the playlist has unsaved changes",
+                    "press y to discard them",
+                )
+                .await
+    }
+
+    /// Asks a yes/no question, waiting a few seconds for the answer.
+    async fn confirm(&mut self, question: &str, hint: &str) -> bool {
+        self.clear();
+        let (w, _) = term::size_or();
+        draw_centered(3, question, None, w, true);
+        draw_centered(4, hint, None, w, false);
+        self.flush();
+
+        self.rx
+            .lock()
+            .await
+            .recv_timeout(Duration::from_secs(2))
+            .is_ok_and(|key| key.code == KeyCode::Char('y'))
+    }
+
     pub fn draw(&mut self, queue: &Queue, player: &Player) {
         let (w, h) = term::size_or();
 
+        if let Some(progress) = &self.progress {
+            draw_centered(0, progress, Some("scanning: "), w, false);
+        }
+
         let current = if let Some(song) = queue.current() {
             &song.name
         } else {
@@ -230,26 +860,349 @@ impl Ui {
             draw_centered(i as u32 + 10, &song.name, None, w, false);
         }
 
+        match self.view {
+            View::Songs => self.draw_songs(queue, w, h),
+            View::Browser => self.draw_browser(queue, w, h),
+            View::Editor => self.draw_editor(w, h),
+            View::Failures => self.draw_failures(queue, w, h),
+            View::Log => self.draw_log(w, h),
+        }
+
+        if let Some(prompt) = &self.prompt {
+            goto::pos(0, 16);
+            clear::line();
+            draw_centered(16, &prompt.text, Some(prompt.label()), w, true);
+        }
+
+        let mut y = h;
+        if let Some(hint) = &self.hint {
+            draw_centered(y, hint, None, w, true);
+            y = y.saturating_sub(1);
+        }
+
+        // the log view already shows them
+        if self.view != View::Log {
+            let now = Local::now();
+            let age = |time: DateTime<Local>| (now - time).to_std().unwrap_or_default();
+            let notices: Vec<_> = self
+                .log
+                .shown(self.min_severity)
+                .rev()
+                .take_while(|record| age(record.time) < ERROR_TIME)
+                .filter(|record| match record.message.level {
+                    Severity::Info => age(record.time) < NOTICE_TIME,
+                    _ => true,
+                })
+                .collect();
+
+            for record in notices.iter().take(MAX_NOTICES) {
+                let pre = severity_label(record.message.level);
+                draw_centered(y, &record.message, pre, w, true);
+                y = y.saturating_sub(1);
+            }
+            // the older ones are above
+            if notices.len() > MAX_NOTICES {
+                let more = format!("+{} more, press L to see them", notices.len() - MAX_NOTICES);
+                draw_centered(y, &more, None, w, false);
+            }
+        }
+    }
+
+    fn draw_songs(&mut self, queue: &Queue, w: u32, h: u32) {
         if let Some(search) = &self.search {
             draw_centered(16, search, Some("search: "), w, true);
-        } else {
+        } else if self.marked.is_empty() {
             draw_centered(16, "songs", None, w, true);
+        } else {
+            let title = format!("songs ({} marked)", self.marked.len());
+            draw_centered(16, &title, None, w, true);
         }
 
-        let selected = self.song_idx - self.songs;
-        for (i, song) in filter_songs(queue.songs(), &self.search)
-            .map(|(_, song)| song)
+        self.refresh_filter(queue);
+        let selected = self.song_idx.saturating_sub(self.songs);
+        for (i, id) in self
+            .filtered
+            .iter()
             .skip(self.songs)
             .enumerate()
-            .take(h as usize - 19)
+            .take((h as usize).saturating_sub(19))
+        {
+            let pre = match (i == selected, self.marked.contains(id)) {
+                (true, true) => Some(">*"),
+                (true, false) => Some("> "),
+                (false, true) => Some("* "),
+                (false, false) => None,
+            };
+
+            let Some(song) = queue.get(*id) else {
+                continue;
+            };
+            draw_centered(i as u32 + 18, &song_name(song), pre, w, i == selected);
+        }
+    }
+
+    fn draw_failures(&mut self, queue: &Queue, w: u32, h: u32) {
+        let broken: Vec<_> = queue.broken().map(|(_, song)| song).collect();
+        let title = format!("unplayable songs ({})", broken.len());
+        draw_centered(16, &title, None, w, true);
+
+        if self.failure_idx >= broken.len() {
+            self.failure_idx = broken.len().saturating_sub(1);
+            self.failures = self.failures.min(self.failure_idx);
+        }
+
+        let selected = self.failure_idx.saturating_sub(self.failures);
+        for (i, song) in broken
+            .iter()
+            .skip(self.failures)
+            .enumerate()
+            .take((h as usize).saturating_sub(19))
         {
+            let line = format!(
+                "{}: {}",
+                song.name,
+                song.broken.as_deref().unwrap_or_default()
+            );
+            let pre = if i == selected { Some("> ") } else { None };
+            draw_centered(i as u32 + 18, &line, pre, w, i == selected);
+        }
+    }
+
+    fn draw_log(&mut self, w: u32, h: u32) {
+        let records: Vec<_> = self.log.shown(self.min_severity).collect();
+        let title = format!("messages ({})", records.len());
+        draw_centered(16, &title, None, w, true);
+
+        let Some(last) = records.len().checked_sub(1) else {
+            return;
+        };
+        self.log_idx = self.log_idx.min(last);
+        let selected = last - self.log_idx;
+
+        // the selected message's details go below the list, which ends with it
+        let detail = if self.log_detail {
+            records[selected].message.detail()
+        } else {
+            String::new()
+        };
+        let detail: Vec<_> = detail.lines().skip(1).collect();
+
+        let rows = (h as usize)
+            .saturating_sub(19)
+            .saturating_sub(detail.len())
+            .max(1);
+        let start = (selected + 1).saturating_sub(rows);
+        let mut y = 18;
+        for (i, record) in records.iter().enumerate().skip(start).take(rows) {
+            let line = format!(
+                "{} {}{}",
+                record.time.format("%H:%M:%S"),
+                severity_label(record.message.level).unwrap_or_default(),
+                &*record.message
+            );
             let pre = if i == selected { Some("> ") } else { None };
+            draw_centered(y, &line, pre, w, i == selected);
+            y += 1;
+        }
 
-            draw_centered(i as u32 + 18, &song.name, pre, w, i == selected);
+        for line in detail {
+            draw_centered(y, line, None, w, false);
+            y += 1;
         }
+    }
+
+    fn draw_browser(&mut self, queue: &Queue, w: u32, h: u32) {
+        self.browser.refresh(queue);
+
+        let pre = match self.browser.grouping() {
+            Grouping::Folder => "folders: ",
+            Grouping::Artist => "artists: ",
+        };
+        draw_centered(16, &self.browser.title(), Some(pre), w, true);
 
-        for (i, message) in self.messages.iter().enumerate() {
-            draw_centered(h - i as u32, message, None, w, true);
+        let node = self.browser.current();
+        let selected = self.browser.node_idx.saturating_sub(self.browser.nodes);
+        for i in 0..(h as usize).saturating_sub(19) {
+            let Some(entry) = node.entry(self.browser.nodes + i) else {
+                break;
+            };
+
+            let name = match entry {
+                Entry::Node(node) => format!("{}/", node.name),
+                Entry::Song(id) => match queue.get(id) {
+                    Some(song) => song_name(song).into_owned(),
+                    None => continue,
+                },
+            };
+
+            let pre = if i == selected { Some("> ") } else { None };
+            draw_centered(i as u32 + 18, &name, pre, w, i == selected);
+        }
+    }
+
+    fn selected(&mut self, queue: &Queue) -> Option<Key> {
+        self.refresh_filter(queue);
+        self.filtered.get(self.song_idx).copied()
+    }
+
+    /// Returns every song matching the current search, if there are any.
+    fn matching(&mut self, queue: &Queue) -> Option<Vec<Key>> {
+        self.refresh_filter(queue);
+        (!self.filtered.is_empty()).then(|| self.filtered.clone())
+    }
+
+    /// Returns the marked songs, in song list order.
+    fn marked_songs(&self, queue: &Queue) -> Vec<Key> {
+        queue
+            .songs()
+            .map(|(id, _)| id)
+            .filter(|id| self.marked.contains(id))
+            .collect()
+    }
+
+    /// Marks every song between the anchor and the selection.
+    fn mark_range(&mut self, queue: &Queue) {
+        let Some(selected) = self.selected(queue) else {
+            return;
+        };
+
+        let anchor = self
+            .mark_anchor
+            .and_then(|anchor| self.filtered.iter().position(|id| *id == anchor))
+            .unwrap_or(self.song_idx);
+
+        let (start, end) = if anchor < self.song_idx {
+            (anchor, self.song_idx)
+        } else {
+            (self.song_idx, anchor)
+        };
+
+        self.marked
+            .extend(self.filtered[start..=end].iter().copied());
+        self.mark_anchor = Some(selected);
+    }
+
+    /// Restores the selection after the filter changed.
+    ///
+    /// Keeps `previous` selected if it still matches, otherwise selects the
+    /// next match after it in the song list, keeping the highlighted row in
+    /// place where possible.
+    fn reselect(&mut self, queue: &Queue, previous: Option<Key>) {
+        self.refresh_filter(queue);
+
+        let idx = previous
+            .and_then(|previous| {
+                self.filtered
+                    .iter()
+                    .position(|id| *id == previous)
+                    .or_else(|| {
+                        let mut filtered = self.filtered.iter().peekable();
+                        let mut idx = 0;
+                        for (id, _) in queue.songs() {
+                            if id == previous {
+                                return Some(idx);
+                            }
+
+                            if filtered.next_if_eq(&&id).is_some() {
+                                idx += 1;
+                            }
+                        }
+
+                        None
+                    })
+            })
+            .unwrap_or(0)
+            .min(self.filtered.len().saturating_sub(1));
+
+        let row = (self.song_idx.saturating_sub(self.songs)).min(5);
+        self.song_idx = idx;
+        self.songs = idx.saturating_sub(row);
+    }
+
+    /// Brings `filtered` up to date with the search query and library.
+    ///
+    /// If the library hasn't changed and the query only got more specific,
+    /// only the previous results are re-checked.
+    fn refresh_filter(&mut self, queue: &Queue) {
+        let fresh = self.filtered_gen == Some(queue.generation());
+        if fresh && self.filtered_query == self.search {
+            return;
+        }
+
+        let narrowing = fresh
+            && match (&self.filtered_query, &self.search) {
+                (Some(old), Some(new)) => new.contains(old.as_str()),
+                (None, _) => true,
+                _ => false,
+            };
+
+        if narrowing {
+            self.filtered.retain(|id| {
+                queue
+                    .get(*id)
+                    .is_some_and(|song| matches_search(song, &self.search))
+            });
+        } else {
+            self.filtered = queue
+                .songs()
+                .filter(|(_, song)| matches_search(song, &self.search))
+                .map(|(id, _)| id)
+                .collect();
+        }
+
+        if !fresh {
+            self.marked.retain(|id| queue.contains(*id));
+        }
+
+        if self.song_idx >= self.filtered.len() {
+            self.song_idx = self.filtered.len().saturating_sub(1);
+            self.songs = self.songs.min(self.song_idx);
+        }
+
+        self.filtered_query.clone_from(&self.search);
+        self.filtered_gen = Some(queue.generation());
+    }
+
+    fn draw_editor(&mut self, w: u32, h: u32) {
+        let Some(editor) = &self.editor else {
+            return;
+        };
+
+        let mut name = editor.name();
+        if editor.modified {
+            name.push('*');
+        }
+        draw_centered(16, &name, Some("playlist: "), w, true);
+
+        if editor.entries.is_empty() {
+            draw_centered(18, "add songs with e from the song list", None, w, false);
+            return;
+        }
+
+        let selected = editor.entry_idx.saturating_sub(editor.entries_scroll);
+        for (i, entry) in editor
+            .entries
+            .iter()
+            .skip(editor.entries_scroll)
+            .enumerate()
+            .take((h as usize).saturating_sub(19))
+        {
+            let mut line = editor::entry_name(&entry.path, entry.title.as_deref());
+            if entry.no_shuffle {
+                line.push_str(" [no shuffle]");
+            }
+            if let Some(next) = &entry.next {
+                let title = editor
+                    .entries
+                    .iter()
+                    .find(|e| e.path == *next)
+                    .and_then(|e| e.title.as_deref());
+                line.push_str(" -> ");
+                line.push_str(&editor::entry_name(next, title));
+            }
+
+            let pre = if i == selected { Some("> ") } else { None };
+            draw_centered(i as u32 + 18, &line, pre, w, i == selected);
         }
     }
 
@@ -257,12 +1210,24 @@ impl Ui {
         clear::all();
     }
 
+    /// Shows how far along a library scan is, or hides it.
+    pub fn set_progress(&mut self, progress: Option<&Progress>) {
+        self.progress = progress
+            .map(|progress| format!("{} folders, {} songs found", progress.dirs, progress.songs));
+    }
+
     pub fn add_message(&mut self, message: Message) {
-        self.messages.push(message);
+        self.log.push(message);
     }
 
     pub fn flush(&self) {
-        if self.search.is_some() {
+        if let Some(prompt) = &self.prompt {
+            let (w, _) = term::size_or();
+            let label = prompt.label().len() as u32;
+            let len = prompt.text.chars().count() as u32;
+            let idx = prompt.text[..prompt.idx].chars().count() as u32;
+            goto::pos((w / 2).saturating_sub((len + label) / 2) + label + idx, 16);
+        } else if self.search.is_some() {
             let (w, _) = term::size_or();
             goto::pos(
                 (w / 2) + (self.search_idx as u32 + 8 + (self.search_idx as u32 % 2)) / 2,
@@ -297,22 +1262,54 @@ fn draw_centered(y: u32, msg: &str, pre: Option<&str>, w: u32, bold: bool) {
     }
 }
 
-fn filter_songs<'a>(
-    songs: impl Iterator<Item = (Key, &'a Song)>,
-    search: &Option<String>,
-) -> impl Iterator<Item = (Key, &'a Song)> {
-    let filter = if let Some(search) = search {
-        let search = search.clone();
-        Box::new(move |(_, song): &(Key, &Song)| {
-            let mut path = song.path.display().to_string();
-            path.make_ascii_lowercase();
-            path.contains(&search)
-        }) as Box<dyn Fn(&(Key, &Song)) -> bool>
+/// Whether a key does the same in every view: playback, switching views and
+/// quitting.
+fn is_global(key: KeyCode) -> bool {
+    matches!(
+        key,
+        KeyCode::Char('q' | ' ' | 'b' | 'F' | 'L' | 'V' | '/') | KeyCode::Left | KeyCode::Right
+    )
+}
+
+/// The label shown before messages of a severity.
+fn severity_label(severity: Severity) -> Option<&'static str> {
+    match severity {
+        Severity::Info => None,
+        Severity::Warning => Some("warning: "),
+        Severity::Error => Some("error: "),
+    }
+}
+
+/// The name to list a song by, noting if it couldn't be played.
+fn song_name(song: &Song) -> Cow<'_, str> {
+    if song.broken.is_some() {
+        Cow::Owned(format!("{} (unplayable)", song.name))
+    } else {
+        Cow::Borrowed(&song.name)
+    }
+}
+
+/// Expands a leading `~/` to the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => path.into(),
+    }
+}
+
+/// Adds the `m3u` extension to a path without one.
+fn m3u_path(path: PathBuf) -> PathBuf {
+    if path.extension().is_none() {
+        path.with_extension("m3u")
     } else {
-        Box::new(|_: &(Key, &Song)| true) as Box<_>
-    };
+        path
+    }
+}
 
-    songs.filter(filter)
+fn matches_search(song: &Song, search: &Option<String>) -> bool {
+    search
+        .as_ref()
+        .is_none_or(|search| song.search_key.contains(search.as_str()))
 }
 
 fn fmt_time(elapsed: f64, total: f64) -> String {
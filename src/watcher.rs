@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+use notify::event::{CreateKind, EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::error::Error;
+use crate::queue::Queue;
+use crate::Message;
+
+/// Watches the directories the library was loaded from for changes.
+pub struct Watcher {
+    watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<notify::Event>>,
+
+    /// The directories being watched.
+    watching: HashSet<PathBuf>,
+    generation: Option<usize>,
+
+    /// The directories created since they started being watched.
+    created: HashSet<PathBuf>,
+    /// The paths found in newly watched directories, to report as changed.
+    found: HashSet<PathBuf>,
+}
+
+impl Watcher {
+    pub fn new() -> notify::Result<Self> {
+        let (tx, rx) = channel();
+        Ok(Self {
+            watcher: notify::recommended_watcher(tx)?,
+            rx,
+
+            watching: HashSet::new(),
+            generation: None,
+
+            created: HashSet::new(),
+            found: HashSet::new(),
+        })
+    }
+
+    /// Starts (or stops) watching directories if the library changed since
+    /// the last call.
+    pub fn sync(&mut self, queue: &Queue) -> Option<Message> {
+        if self.generation == Some(queue.generation()) {
+            return None;
+        }
+        self.generation = Some(queue.generation());
+
+        let dirs: HashSet<_> = queue.dirs().map(Path::to_path_buf).collect();
+        for dir in self.watching.difference(&dirs) {
+            // deleted directories stop being watched on their own
+            let _ = self.watcher.unwatch(dir);
+        }
+        self.created.retain(|dir| dirs.contains(dir));
+
+        let mut failed = 0;
+        let mut error = None;
+        for dir in dirs.difference(&self.watching) {
+            if let Err(e) = self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                failed += 1;
+                error.get_or_insert(e);
+            } else if self.created.iter().any(|created| dir.starts_with(created)) {
+                // files put in a new directory before it was watched only
+                // show up if it's read again
+                let entries = fs::read_dir(dir).into_iter().flatten().flatten();
+                self.found.extend(entries.map(|entry| entry.path()));
+            }
+        }
+        self.watching = dirs;
+
+        error.map(|source| {
+            Error::Watch {
+                dirs: failed,
+                source,
+            }
+            .into()
+        })
+    }
+
+    /// Returns every path created, removed, renamed or written to since the
+    /// last call.
+    pub fn changes(&mut self) -> HashSet<PathBuf> {
+        let mut paths = std::mem::take(&mut self.found);
+        for event in self.rx.try_iter().flatten() {
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_)
+            ) || matches!(event.kind, EventKind::Modify(ModifyKind::Metadata(_)))
+            {
+                continue;
+            }
+
+            if event.kind == EventKind::Create(CreateKind::Folder) {
+                self.created.extend(event.paths.iter().cloned());
+            }
+
+            paths.extend(event.paths);
+        }
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Waits for the watcher to report `path` as changed.
+    fn wait_for(watcher: &mut Watcher, path: &Path) {
+        let start = Instant::now();
+        let mut changes = HashSet::new();
+        while !changes.contains(path) {
+            assert!(start.elapsed() < Duration::from_secs(5), "no change seen");
+            std::thread::sleep(Duration::from_millis(10));
+            changes.extend(watcher.changes());
+        }
+    }
+
+    #[test]
+    fn reads_new_directories_once_watched() {
+        let root = std::env::temp_dir().join(format!("cramp-watcher-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        let mut queue = Queue::default();
+        queue.load(&root).for_each(drop);
+        let mut watcher = Watcher::new().unwrap();
+        assert!(watcher.sync(&queue).is_none());
+
+        let dir = root.join("new");
+        fs::create_dir(&dir).unwrap();
+        wait_for(&mut watcher, &dir);
+        queue.load(&dir).for_each(drop);
+
+        // written after the folder was loaded, but before it was watched
+        let song = dir.join("song.mp3");
+        fs::write(&song, b"").unwrap();
+        assert!(watcher.sync(&queue).is_none());
+        let changes = watcher.changes();
+
+        fs::remove_dir_all(&root).unwrap();
+        assert!(changes.contains(&song));
+    }
+}